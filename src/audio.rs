@@ -1,54 +1,573 @@
+mod channels;
+mod file;
+mod gate;
+mod level;
+mod normalizer;
+mod pipeline;
+mod pitch;
+mod spectrum;
+mod transport;
+mod vad;
+mod viseme;
+
+pub use channels::ChannelMix;
 use cpal::{
-    InputCallbackInfo, SampleFormat, Stream,
+    BuildStreamError, Device, FromSample, Host, I24, InputCallbackInfo, SampleFormat, SizedSample,
+    Stream, StreamConfig, SupportedStreamConfig,
     traits::{DeviceTrait, HostTrait, StreamTrait},
 };
-use std::sync::{Arc, Mutex};
+#[cfg(test)]
+pub use file::write_test_wav;
+pub use file::{FileConfig, Pacing, analyze_file};
+pub use gate::NoiseGate;
+use level::LevelConfig;
+pub use normalizer::IntensityThresholds;
+use normalizer::NormalizerConfig;
+use pipeline::Pipeline;
+use pitch::PitchConfig;
+use serde::Deserialize;
+use std::{
+    fmt::{self, Display},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::Duration,
+};
+pub use transport::AnalysisFrame;
+use transport::{FrameQueue, FrameReceiver, FrameSender, QueueHandover};
+use vad::VadConfig;
+pub use viseme::Viseme;
+use viseme::VisemeConfig;
+
+/// How often the input thread checks whether the input device should be switched or reconnected.
+const DEVICE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The level reported while no input stream is running, in dBFS.
+const SILENCE_DBFS: f32 = f32::NEG_INFINITY;
 
+/// The UI's view of the audio input.
 pub struct AudioState {
-    pub volume: Arc<Mutex<f32>>,
+    /// Frames analyzed by the audio thread.
+    frames: FrameReceiver,
+
+    /// The most recent frame received.
+    latest: AnalysisFrame,
+
+    /// Whether an input device is currently attached.
+    connected: Arc<AtomicBool>,
+}
+
+impl AudioState {
+    /// Returns a state with no input attached, which reports silence until the returned link
+    /// attaches a device.
+    fn disabled() -> (Self, AudioLink) {
+        let (queues, frames) = transport::frame_channel();
+        let connected = Arc::new(AtomicBool::new(false));
+
+        let state = Self {
+            frames,
+            latest: AnalysisFrame::SILENT,
+            connected: connected.clone(),
+        };
+        (state, AudioLink { queues, connected })
+    }
+
+    /// Receives every frame analyzed since the last call, oldest first. This should be called
+    /// once per UI frame.
+    pub fn poll(&mut self) -> Vec<AnalysisFrame> {
+        let frames = self.frames.receive();
+        self.latest = if self.is_connected() {
+            frames.last().copied().unwrap_or(self.latest)
+        } else {
+            AnalysisFrame::SILENT
+        };
+        frames
+    }
+
+    /// Returns the most recent frame received by `poll`.
+    pub fn latest(&self) -> AnalysisFrame {
+        self.latest
+    }
+
+    /// Returns whether an input device is currently attached.
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+}
+
+/// The input thread's end of an `AudioState`.
+struct AudioLink {
+    queues: QueueHandover,
+    connected: Arc<AtomicBool>,
+}
+
+impl AudioLink {
+    /// Records whether a device is attached.
+    fn set_connected(&self, connected: bool) {
+        self.connected.store(connected, Ordering::Relaxed);
+    }
+}
+
+/// Settings for audio input.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    /// The input device to use. Falls back to the default input device if not set or not found.
+    pub device: Option<DeviceSelector>,
+
+    /// The channels of the input that feed the character, counting from 1. Every channel is used
+    /// if this is empty.
+    pub channels: Vec<usize>,
+
+    /// How the channels feeding the character are combined.
+    pub mix: ChannelMix,
+
+    /// A WAV file to read input from instead of a device, such as a recording of a bug report.
+    pub file: Option<FileConfig>,
+
+    /// How the loudness of the input is measured.
+    pub level: LevelConfig,
+
+    /// Settings for normalizing the level to the speaker's usual speech level.
+    pub normalizer: NormalizerConfig,
+
+    /// Settings for telling speech apart from other sounds.
+    pub vad: VadConfig,
+
+    /// Settings for recognizing mouth shapes from the voice.
+    pub visemes: VisemeConfig,
+
+    /// Settings for tracking the pitch of the voice.
+    pub pitch: PitchConfig,
 }
 
-/// Ensure to store the resulting `Stream`! It will be dropped otherwise and mic input will stop.
-pub fn start_default_stream() -> (AudioState, Stream) {
+/// Identifies an input device, either by name or by its index in the device list.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum DeviceSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl DeviceSelector {
+    /// Parses a selector from a string, which is treated as an index if it is a number.
+    pub fn parse(s: &str) -> Self {
+        s.parse()
+            .map(Self::Index)
+            .unwrap_or_else(|_| Self::Name(s.to_string()))
+    }
+
+    /// Finds the selected device among the host's input devices. Names are matched exactly
+    /// first, then case-insensitively by substring.
+    fn find(&self, host: &Host) -> Option<Device> {
+        let mut devices = host.input_devices().ok()?;
+        match self {
+            Self::Index(index) => devices.nth(*index),
+            Self::Name(name) => {
+                let devices: Vec<_> = devices.collect();
+                let lowercase_name = name.to_lowercase();
+                devices
+                    .iter()
+                    .find(|d| d.name().is_ok_and(|n| n == *name))
+                    .or_else(|| {
+                        devices.iter().find(|d| {
+                            d.name()
+                                .is_ok_and(|n| n.to_lowercase().contains(&lowercase_name))
+                        })
+                    })
+                    .cloned()
+            }
+        }
+    }
+}
+
+impl Display for DeviceSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "#{index}"),
+            Self::Name(name) => write!(f, "'{name}'"),
+        }
+    }
+}
+
+/// An input device, as printed by `--list-devices`.
+pub struct InputDeviceInfo {
+    pub name: String,
+    pub is_default: bool,
+    pub config: Option<SupportedStreamConfig>,
+}
+
+impl Display for InputDeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(config) = &self.config {
+            write!(
+                f,
+                " ({} ch, {} Hz, {})",
+                config.channels(),
+                config.sample_rate().0,
+                config.sample_format()
+            )?;
+        }
+        if self.is_default {
+            write!(f, " [default]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the input devices available on the default host, in the order used by
+/// `DeviceSelector::Index`.
+pub fn list_input_devices() -> Result<Vec<InputDeviceInfo>, cpal::DevicesError> {
     let host = cpal::default_host();
+    let default_name = host.default_input_device().and_then(|d| d.name().ok());
 
-    let device = host
-        .default_input_device()
-        .expect("no input device available");
+    Ok(host
+        .input_devices()?
+        .map(|device| {
+            let name = device.name().unwrap_or_else(|_| "<unknown>".to_string());
+            InputDeviceInfo {
+                is_default: default_name.as_ref() == Some(&name),
+                config: device.default_input_config().ok(),
+                name,
+            }
+        })
+        .collect())
+}
+
+/// Handle to the audio input threads. Each device gets one thread, which owns its input stream
+/// and rebuilds it whenever the device disappears, reappears, or the stream fails. While no device
+/// can be opened, the states stay disabled and the thread keeps retrying in the background.
+/// Characters reading a file get a thread of their own instead. Dropping this handle stops the
+/// threads.
+pub struct AudioInput {
+    _stops: Vec<mpsc::Sender<()>>,
+}
 
+/// A character listening to an input stream.
+struct Listener {
+    config: AudioConfig,
+    link: AudioLink,
+}
+
+impl AudioInput {
+    /// Starts listening on the configured input files or devices, falling back to the default
+    /// input device, and returns a state for each of the given settings in the same order.
+    /// Settings that select the same device share one input stream, since many devices can only
+    /// be opened once. This never fails; if there is no usable input yet, the returned states
+    /// report silence until one is attached.
+    pub fn start(configs: Vec<AudioConfig>) -> (Vec<AudioState>, Self) {
+        let mut states = Vec::new();
+        let mut stops = Vec::new();
+        let mut listeners = Vec::new();
+
+        for config in configs {
+            let (state, link) = AudioState::disabled();
+            states.push(state);
+
+            if let Some(file) = config.file.clone() {
+                stops.push(spawn_input_thread(move |stopped| {
+                    file::run_file_thread(config, file, link, stopped)
+                }));
+                continue;
+            }
+
+            listeners.push(Listener { config, link });
+        }
+
+        for (device, listeners) in group_by_device(listeners) {
+            stops.push(spawn_input_thread(move |stopped| {
+                run_input_thread(device, listeners, stopped)
+            }));
+        }
+
+        (states, Self { _stops: stops })
+    }
+}
+
+/// Groups the given listeners by the device they select, keeping their order.
+fn group_by_device(listeners: Vec<Listener>) -> Vec<(Option<DeviceSelector>, Vec<Listener>)> {
+    let mut devices: Vec<(Option<DeviceSelector>, Vec<Listener>)> = Vec::new();
+    for listener in listeners {
+        match devices
+            .iter_mut()
+            .find(|(device, _)| *device == listener.config.device)
+        {
+            Some((_, listeners)) => listeners.push(listener),
+            None => devices.push((listener.config.device.clone(), vec![listener])),
+        }
+    }
+    devices
+}
+
+/// Runs the given function on a new input thread, passing it a receiver that is disconnected once
+/// the returned sender is dropped.
+fn spawn_input_thread(run: impl FnOnce(mpsc::Receiver<()>) + Send + 'static) -> mpsc::Sender<()> {
+    let (stop, stopped) = mpsc::channel();
+    if let Err(e) = thread::Builder::new()
+        .name("audio input".to_string())
+        .spawn(move || run(stopped))
+    {
+        eprintln!("couldn't start audio input thread, continuing without a mic: {e}");
+    }
+    stop
+}
+
+/// Keeps an input stream open on the best available device for the given listeners until
+/// `stopped` is disconnected.
+fn run_input_thread(
+    selector: Option<DeviceSelector>,
+    listeners: Vec<Listener>,
+    stopped: mpsc::Receiver<()>,
+) {
+    let host = cpal::default_host();
+    let failed = Arc::new(AtomicBool::new(false));
+    let mut current: Option<(Stream, String)> = None;
+    let mut warned_missing = false;
+    let mut last_error = None;
+    let set_connected = |connected| {
+        for listener in &listeners {
+            listener.link.set_connected(connected);
+        }
+    };
+
+    loop {
+        if failed.swap(false, Ordering::Relaxed) && current.take().is_some() {
+            set_connected(false);
+        }
+
+        // prefer the selected device, but fall back to the default one while it's missing
+        let selected = selector.as_ref().and_then(|s| s.find(&host));
+        if let Some(selector) = &selector {
+            if selected.is_none() && !warned_missing {
+                eprintln!("input device {selector} not found, using the default device");
+            }
+            warned_missing = selected.is_none();
+        }
+
+        match selected.or_else(|| host.default_input_device()) {
+            Some(device) => {
+                let name = device.name().unwrap_or_default();
+                if current.as_ref().is_none_or(|(_, n)| *n != name) {
+                    match open_stream(&device, &listeners, failed.clone()) {
+                        Ok((stream, queues)) => {
+                            eprintln!("listening on input device '{name}'");
+                            for (listener, queue) in listeners.iter().zip(queues) {
+                                listener.link.queues.hand_over(queue);
+                            }
+                            set_connected(true);
+                            current = Some((stream, name));
+                            last_error = None;
+                        }
+                        Err(e) => report_once(
+                            &mut last_error,
+                            format!("couldn't open input device '{name}': {e}"),
+                        ),
+                    }
+                }
+            }
+            None => {
+                current = None;
+                set_connected(false);
+                report_once(&mut last_error, "no input device available".to_string());
+            }
+        }
+
+        match stopped.recv_timeout(DEVICE_CHECK_INTERVAL) {
+            Err(RecvTimeoutError::Timeout) => continue,
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+/// Prints the given problem unless it is the same as the last one reported, since the input
+/// thread retries every `DEVICE_CHECK_INTERVAL`.
+fn report_once(last_error: &mut Option<String>, message: String) {
+    if last_error.as_ref() != Some(&message) {
+        eprintln!("{message}; retrying in the background");
+        *last_error = Some(message);
+    }
+}
+
+/// Errors that can occur while opening an input stream.
+#[derive(Debug)]
+enum OpenStreamError {
+    Config(cpal::DefaultStreamConfigError),
+    Build(BuildStreamError),
+    Play(cpal::PlayStreamError),
+}
+
+impl Display for OpenStreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(e) => write!(f, "no input config available: {e}"),
+            Self::Build(e) => write!(f, "failed to build input stream: {e}"),
+            Self::Play(e) => write!(f, "failed to play input stream: {e}"),
+        }
+    }
+}
+
+/// Opens and starts an input stream on the given device, with a pipeline and frame queue for each
+/// of the given listeners. `failed` is set when the stream reports an error, such as the device
+/// being unplugged.
+fn open_stream(
+    device: &Device,
+    listeners: &[Listener],
+    failed: Arc<AtomicBool>,
+) -> Result<(Stream, Vec<FrameQueue>), OpenStreamError> {
     let config = device
         .default_input_config()
-        .expect("no input config available");
+        .map_err(OpenStreamError::Config)?;
 
     let sample_format = config.sample_format();
+    let config: StreamConfig = config.into();
 
-    let volume = Arc::new(Mutex::new(0.0));
-    let volume_clone = volume.clone();
-    let err_fn = |e| eprintln!("error occurred on stream: {}", e);
+    let (output, queues): (Vec<_>, Vec<_>) = listeners
+        .iter()
+        .map(|listener| {
+            let (sender, queue) = transport::frame_queue();
+            let pipeline = Pipeline::new(&listener.config, config.sample_rate.0, config.channels);
+            ((pipeline, sender), queue)
+        })
+        .unzip();
     let stream = match sample_format {
-        SampleFormat::F32 => device.build_input_stream(
-            &config.into(),
-            move |data: &[f32], _: &InputCallbackInfo| {
-                let max_sample_value = data
-                    .iter()
-                    .map(|sample| (sample.abs() * u16::MAX as f32) as u16)
-                    .max()
-                    .unwrap_or(0);
-                *volume_clone.lock().unwrap() = u16_to_dbfs(max_sample_value)
-            },
-            err_fn,
-            None,
-        ),
-        _ => unimplemented!(),
+        SampleFormat::I8 => build_input_stream::<i8>(device, &config, output, failed),
+        SampleFormat::I16 => build_input_stream::<i16>(device, &config, output, failed),
+        SampleFormat::I24 => build_input_stream::<I24>(device, &config, output, failed),
+        SampleFormat::I32 => build_input_stream::<i32>(device, &config, output, failed),
+        SampleFormat::I64 => build_input_stream::<i64>(device, &config, output, failed),
+        SampleFormat::U8 => build_input_stream::<u8>(device, &config, output, failed),
+        SampleFormat::U16 => build_input_stream::<u16>(device, &config, output, failed),
+        SampleFormat::U32 => build_input_stream::<u32>(device, &config, output, failed),
+        SampleFormat::U64 => build_input_stream::<u64>(device, &config, output, failed),
+        SampleFormat::F32 => build_input_stream::<f32>(device, &config, output, failed),
+        SampleFormat::F64 => build_input_stream::<f64>(device, &config, output, failed),
+        _ => Err(BuildStreamError::StreamConfigNotSupported),
     }
-    .unwrap();
+    .map_err(OpenStreamError::Build)?;
+
+    stream.play().map_err(OpenStreamError::Play)?;
+
+    Ok((stream, queues))
+}
 
-    stream.play().expect("failed to play stream");
+/// Builds an input stream for samples of type `T`. Every buffer is fed through each pipeline with
+/// `process_samples` and the results are sent to the UI.
+fn build_input_stream<T>(
+    device: &Device,
+    config: &StreamConfig,
+    mut output: Vec<(Pipeline, FrameSender)>,
+    failed: Arc<AtomicBool>,
+) -> Result<Stream, BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let err_fn = move |e| {
+        eprintln!("error occurred on stream: {}", e);
+        failed.store(true, Ordering::Relaxed);
+    };
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &InputCallbackInfo| {
+            for (pipeline, sender) in &mut output {
+                sender.send(process_samples(pipeline, data));
+            }
+        },
+        err_fn,
+        None,
+    )
+}
 
-    (AudioState { volume }, stream)
+/// Converts a buffer of samples of type `T` to `f32` and analyzes it, so that every device is
+/// measured the same way regardless of its native sample format.
+fn process_samples<T>(pipeline: &mut Pipeline, data: &[T]) -> AnalysisFrame
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    pipeline.process(data.iter().map(|&s| s.to_sample::<f32>()))
 }
 
-fn u16_to_dbfs(volume: u16) -> f32 {
-    let normalized = volume as f32 / u16::MAX as f32;
-    20.0 * normalized.log10()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    /// Returns the level of a second of a square wave with the given amplitude, in samples of
+    /// type `T`.
+    fn square_wave_dbfs<T>(amplitude: f32) -> f32
+    where
+        T: SizedSample + FromSample<f32>,
+        f32: FromSample<T>,
+    {
+        let mut pipeline = Pipeline::new(&AudioConfig::default(), SAMPLE_RATE, 1);
+        let data: Vec<T> = (0..SAMPLE_RATE)
+            .map(|i| T::from_sample(if i % 2 == 0 { amplitude } else { -amplitude }))
+            .collect();
+        process_samples(&mut pipeline, &data).level_dbfs
+    }
+
+    fn assert_levels<T>()
+    where
+        T: SizedSample + FromSample<f32>,
+        f32: FromSample<T>,
+    {
+        let format = std::any::type_name::<T>();
+        let full = square_wave_dbfs::<T>(1.0);
+        assert!(full.abs() < 0.1, "full scale {format} measured {full} dBFS");
+        let half = square_wave_dbfs::<T>(0.5);
+        assert!(
+            (half + 6.02).abs() < 0.1,
+            "half scale {format} measured {half} dBFS"
+        );
+    }
+
+    #[test]
+    fn listeners_on_the_same_device_share_it() {
+        let listener = |device: Option<&str>, channel| Listener {
+            config: AudioConfig {
+                device: device.map(DeviceSelector::parse),
+                channels: vec![channel],
+                ..Default::default()
+            },
+            link: AudioState::disabled().1,
+        };
+        let devices = group_by_device(vec![
+            listener(Some("Scarlett 2i2"), 1),
+            listener(None, 1),
+            listener(Some("Scarlett 2i2"), 2),
+        ]);
+
+        let groups: Vec<_> = devices
+            .iter()
+            .map(|(device, listeners)| {
+                let channels: Vec<_> = listeners.iter().map(|l| l.config.channels[0]).collect();
+                (device.clone(), channels)
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                (Some(DeviceSelector::parse("Scarlett 2i2")), vec![1, 2]),
+                (None, vec![1]),
+            ]
+        );
+    }
+
+    #[test]
+    fn every_sample_format_measures_the_same() {
+        assert_levels::<i8>();
+        assert_levels::<i16>();
+        assert_levels::<I24>();
+        assert_levels::<i32>();
+        assert_levels::<u8>();
+        assert_levels::<u16>();
+        assert_levels::<f32>();
+        assert_levels::<f64>();
+    }
 }
@@ -0,0 +1,142 @@
+use serde::Deserialize;
+
+use super::{SILENCE_DBFS, level};
+
+/// The most channels that get their own meter. Channels past this still feed the character if
+/// selected, they just aren't metered.
+pub const MAX_METERED_CHANNELS: usize = 8;
+
+/// How the selected channels of a multi-channel input are combined into the one signal that
+/// drives the character.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChannelMix {
+    /// The average of the selected channels.
+    #[default]
+    Average,
+
+    /// Whichever selected channel is loudest at each sample.
+    Loudest,
+}
+
+/// The peak level of each channel in a block of input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ChannelLevels {
+    peaks_dbfs: [f32; MAX_METERED_CHANNELS],
+    selected: [bool; MAX_METERED_CHANNELS],
+    len: usize,
+}
+
+impl ChannelLevels {
+    /// Levels for an input without any channels.
+    pub const EMPTY: Self = Self {
+        peaks_dbfs: [SILENCE_DBFS; MAX_METERED_CHANNELS],
+        selected: [false; MAX_METERED_CHANNELS],
+        len: 0,
+    };
+
+    /// Returns the peak level of each channel, in dBFS.
+    pub fn peaks_dbfs(&self) -> &[f32] {
+        &self.peaks_dbfs[..self.len]
+    }
+
+    /// Returns whether each channel feeds the character.
+    pub fn selected(&self) -> &[bool] {
+        &self.selected[..self.len]
+    }
+}
+
+/// Combines interleaved samples into a mono signal from the selected channels, measuring the
+/// level of every channel along the way.
+pub struct ChannelMixer {
+    /// Whether each channel of the input feeds the mix.
+    selected: Vec<bool>,
+
+    /// The number of channels that feed the mix.
+    selected_count: usize,
+
+    mode: ChannelMix,
+
+    /// The combination of the current sample's channels so far.
+    mix: f32,
+
+    /// The channel of the next sample.
+    channel: usize,
+
+    /// The highest absolute sample of each metered channel in the current block.
+    peaks: [f32; MAX_METERED_CHANNELS],
+}
+
+impl ChannelMixer {
+    /// Creates a mixer for an input with the given number of channels. `selection` lists the
+    /// channels to mix, counting from 1; if it's empty or lists no channel the input has, every
+    /// channel is mixed.
+    pub fn new(channels: u16, selection: &[usize], mode: ChannelMix) -> Self {
+        let channels = channels.max(1) as usize;
+        let mut selected: Vec<bool> = (1..=channels).map(|c| selection.contains(&c)).collect();
+
+        if !selection.is_empty() && !selected.contains(&true) {
+            eprintln!(
+                "none of the selected channels {selection:?} exist on an input with {channels} \
+                 channels, using all of them"
+            );
+        }
+        if !selected.contains(&true) {
+            selected.fill(true);
+        }
+
+        Self {
+            selected_count: selected.iter().filter(|&&s| s).count(),
+            selected,
+            mode,
+            mix: 0.0,
+            channel: 0,
+            peaks: [0.0; MAX_METERED_CHANNELS],
+        }
+    }
+
+    /// Adds the next interleaved sample, returning the mixed sample once every channel of the
+    /// current sample has been added.
+    pub fn push(&mut self, sample: f32) -> Option<f32> {
+        if let Some(peak) = self.peaks.get_mut(self.channel) {
+            *peak = peak.max(sample.abs());
+        }
+
+        if self.selected[self.channel] {
+            self.mix = match self.mode {
+                ChannelMix::Average => self.mix + sample,
+                ChannelMix::Loudest if sample.abs() > self.mix.abs() => sample,
+                ChannelMix::Loudest => self.mix,
+            };
+        }
+
+        self.channel += 1;
+        if self.channel < self.selected.len() {
+            return None;
+        }
+
+        let mixed = match self.mode {
+            ChannelMix::Average => self.mix / self.selected_count as f32,
+            ChannelMix::Loudest => self.mix,
+        };
+        self.mix = 0.0;
+        self.channel = 0;
+        Some(mixed)
+    }
+
+    /// Returns the peak level of each channel since the last call.
+    pub fn take_levels(&mut self) -> ChannelLevels {
+        let mut levels = ChannelLevels {
+            len: self.selected.len().min(MAX_METERED_CHANNELS),
+            ..ChannelLevels::EMPTY
+        };
+        for (level, peak) in levels.peaks_dbfs.iter_mut().zip(&mut self.peaks) {
+            *level = level::amplitude_to_dbfs(*peak);
+            *peak = 0.0;
+        }
+        for (level, &selected) in levels.selected.iter_mut().zip(&self.selected) {
+            *level = selected;
+        }
+        levels
+    }
+}
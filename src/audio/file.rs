@@ -0,0 +1,255 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError, TryRecvError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use hound::{SampleFormat, WavReader};
+use serde::Deserialize;
+
+use super::{AudioConfig, AudioLink, pipeline::Pipeline, transport, transport::AnalysisFrame};
+
+/// The length of the blocks a file is read in, in seconds. This is about as long as the buffers a
+/// microphone delivers, so file input is analyzed the same way live input is.
+const BLOCK_SECS: f64 = 0.01;
+
+/// Settings for reading input from a WAV file instead of a device.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    /// The WAV file to read.
+    pub path: PathBuf,
+
+    /// How quickly the file is read.
+    #[serde(default)]
+    pub pacing: Pacing,
+
+    /// Whether to start over from the beginning once the file ends.
+    #[serde(default)]
+    pub looping: bool,
+}
+
+/// How quickly a file is read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pacing {
+    /// As quickly as the file would play, as if it were coming from a microphone.
+    #[default]
+    RealTime,
+
+    /// As quickly as it can be analyzed.
+    AsFastAsPossible,
+}
+
+/// A WAV file read as normalized, interleaved samples.
+pub struct WavFile {
+    reader: WavReader<BufReader<File>>,
+}
+
+impl WavFile {
+    pub fn open(path: &Path) -> Result<Self, hound::Error> {
+        Ok(Self {
+            reader: WavReader::open(path)?,
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.reader.spec().sample_rate
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.reader.spec().channels
+    }
+
+    /// Replaces the contents of `buf` with up to `frames` samples per channel from the file.
+    /// Returns `false` once the end of the file is reached.
+    pub fn read(&mut self, frames: usize, buf: &mut Vec<f32>) -> Result<bool, hound::Error> {
+        let spec = self.reader.spec();
+        let len = frames * spec.channels as usize;
+        buf.clear();
+
+        match spec.sample_format {
+            SampleFormat::Float => {
+                for sample in self.reader.samples::<f32>().take(len) {
+                    buf.push(sample?);
+                }
+            }
+            SampleFormat::Int => {
+                let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+                for sample in self.reader.samples::<i32>().take(len) {
+                    buf.push(sample? as f32 * scale);
+                }
+            }
+        }
+
+        Ok(!buf.is_empty())
+    }
+
+    /// Goes back to the start of the file.
+    pub fn rewind(&mut self) -> Result<(), hound::Error> {
+        Ok(self.reader.seek(0)?)
+    }
+}
+
+/// Analyzes a whole WAV file as quickly as possible, returning every frame in order. This drives
+/// the same analysis as live input, without needing a microphone.
+pub fn analyze_file(path: &Path, config: &AudioConfig) -> Result<Vec<AnalysisFrame>, hound::Error> {
+    let mut file = WavFile::open(path)?;
+    let mut pipeline = Pipeline::new(config, file.sample_rate(), file.channels());
+    let block_frames = block_frames(file.sample_rate());
+
+    let mut frames = Vec::new();
+    let mut buf = Vec::new();
+    while file.read(block_frames, &mut buf)? {
+        frames.push(pipeline.process(buf.iter().copied()));
+    }
+
+    Ok(frames)
+}
+
+/// Returns the number of samples per channel in a block.
+fn block_frames(sample_rate: u32) -> usize {
+    ((sample_rate as f64 * BLOCK_SECS) as usize).max(1)
+}
+
+/// Feeds a file through the pipeline until it ends or `stopped` is disconnected.
+pub(super) fn run_file_thread(
+    config: AudioConfig,
+    file_config: FileConfig,
+    link: AudioLink,
+    stopped: mpsc::Receiver<()>,
+) {
+    let path = &file_config.path;
+    let mut file = match WavFile::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("couldn't open {}: {e}", path.display());
+            return;
+        }
+    };
+
+    let sample_rate = file.sample_rate();
+    let mut pipeline = Pipeline::new(&config, sample_rate, file.channels());
+    let (mut sender, queue) = transport::frame_queue();
+    link.queues.hand_over(queue);
+    link.set_connected(true);
+    eprintln!("reading input from {}", path.display());
+
+    let block_frames = block_frames(sample_rate);
+    let mut buf = Vec::with_capacity(block_frames * file.channels() as usize);
+    let start = Instant::now();
+    let mut position = 0;
+    let mut rewound = false;
+
+    loop {
+        match stopped.try_recv() {
+            Err(TryRecvError::Empty) => {}
+            Ok(()) | Err(TryRecvError::Disconnected) => return,
+        }
+
+        match file.read(block_frames, &mut buf) {
+            Ok(true) => rewound = false,
+            Ok(false) if rewound => {
+                eprintln!("{} has no samples to loop", path.display());
+                break;
+            }
+            Ok(false) if file_config.looping => match file.rewind() {
+                Ok(()) => {
+                    rewound = true;
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("couldn't rewind {}: {e}", path.display());
+                    break;
+                }
+            },
+            Ok(false) => {
+                eprintln!("reached the end of {}", path.display());
+                break;
+            }
+            Err(e) => {
+                eprintln!("couldn't read {}: {e}", path.display());
+                break;
+            }
+        }
+
+        let frame = pipeline.process(buf.iter().copied());
+        position += buf.len() / file.channels() as usize;
+
+        match file_config.pacing {
+            Pacing::RealTime => {
+                // wait until the block would have finished playing, so the app sees the file at
+                // the speed it was recorded
+                let played = Duration::from_secs_f64(position as f64 / sample_rate as f64);
+                thread::sleep(played.saturating_sub(start.elapsed()));
+            }
+            Pacing::AsFastAsPossible => {
+                // the UI drains the queue every frame, so wait for room rather than dropping frames
+                while sender.is_full()
+                    && stopped.recv_timeout(Duration::from_millis(1))
+                        == Err(RecvTimeoutError::Timeout)
+                {}
+            }
+        }
+        sender.send(frame);
+    }
+
+    link.set_connected(false);
+}
+
+/// Writes the given samples to a mono 16-bit WAV file in the temp directory, and returns its path.
+#[cfg(test)]
+pub fn write_test_wav(
+    name: &str,
+    sample_rate: u32,
+    samples: impl IntoIterator<Item = f32>,
+) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("muni-tuber-{name}-{}.wav", std::process::id()));
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+    for sample in samples {
+        writer
+            .write_sample((sample * i16::MAX as f32) as i16)
+            .unwrap();
+    }
+    writer.finalize().unwrap();
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::audio::AudioState;
+
+    #[test]
+    fn looping_an_empty_file_stops() {
+        let path = write_test_wav("empty", 48000, []);
+
+        let file_config = FileConfig {
+            path: path.clone(),
+            pacing: Pacing::AsFastAsPossible,
+            looping: true,
+        };
+        let (_state, link) = AudioState::disabled();
+        let (_stop, stopped) = mpsc::channel();
+        let (done, finished) = mpsc::channel();
+        thread::spawn(move || {
+            run_file_thread(Default::default(), file_config, link, stopped);
+            done.send(()).unwrap();
+        });
+
+        let result = finished.recv_timeout(Duration::from_secs(5));
+        fs::remove_file(&path).ok();
+        assert!(result.is_ok(), "the file thread kept looping");
+    }
+}
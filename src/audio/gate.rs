@@ -0,0 +1,41 @@
+use super::{SILENCE_DBFS, transport::AnalysisFrame};
+
+/// How far below its threshold the level must fall before an open gate closes again, in dB.
+const GATE_HYSTERESIS_DB: f32 = 3.0;
+
+/// Silences levels that don't rise above the background noise, such as fans and room hum.
+#[derive(Debug, Default)]
+pub struct NoiseGate {
+    /// The level the input must exceed for the gate to open, in dBFS. `None` disables the gate.
+    threshold_dbfs: Option<f32>,
+
+    /// Whether the gate is currently letting the level through.
+    is_open: bool,
+}
+
+impl NoiseGate {
+    pub fn set_threshold(&mut self, threshold_dbfs: Option<f32>) {
+        self.threshold_dbfs = threshold_dbfs;
+    }
+
+    /// Opens or closes the gate for a newly measured level.
+    pub fn process(&mut self, level_dbfs: f32) {
+        let Some(threshold) = self.threshold_dbfs else {
+            return;
+        };
+
+        self.is_open = if self.is_open {
+            level_dbfs > threshold - GATE_HYSTERESIS_DB
+        } else {
+            level_dbfs > threshold
+        };
+    }
+
+    /// Silences the given frame's level and intensity if the gate is closed.
+    pub fn apply(&self, frame: &mut AnalysisFrame) {
+        if self.threshold_dbfs.is_some() && !self.is_open {
+            frame.level_dbfs = SILENCE_DBFS;
+            frame.intensity = frame.intensity.map(|_| 0.0);
+        }
+    }
+}
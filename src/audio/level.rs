@@ -0,0 +1,167 @@
+use serde::Deserialize;
+
+/// Settings for the level detector.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LevelConfig {
+    /// The length of the window the RMS level is measured over, in milliseconds.
+    pub window_ms: f32,
+
+    /// How long the level takes to rise towards a louder signal, in milliseconds.
+    pub attack_ms: f32,
+
+    /// How long the level takes to fall towards a quieter signal, in milliseconds.
+    pub release_ms: f32,
+}
+
+impl Default for LevelConfig {
+    fn default() -> Self {
+        Self {
+            window_ms: 30.0,
+            attack_ms: 10.0,
+            release_ms: 150.0,
+        }
+    }
+}
+
+/// Measures the loudness of a signal as the RMS over a sliding window, smoothed by an
+/// attack/release envelope so that single clicks don't register as speech and the level doesn't
+/// drop out between syllables.
+pub struct LevelDetector {
+    /// Squared samples in the current window, used as a ring buffer.
+    squares: Vec<f32>,
+
+    /// The index in `squares` that the next sample is written to.
+    position: usize,
+
+    /// The sum of `squares`.
+    sum: f64,
+
+    /// Smoothing coefficient used while the level is rising.
+    attack_coeff: f32,
+
+    /// Smoothing coefficient used while the level is falling.
+    release_coeff: f32,
+
+    /// The smoothed RMS level, as a linear amplitude.
+    envelope: f32,
+}
+
+impl LevelDetector {
+    /// Creates a detector for a signal with the given number of samples per second.
+    pub fn new(config: &LevelConfig, sample_rate: u32) -> Self {
+        let window_len = ms_to_samples(config.window_ms, sample_rate).max(1.0) as usize;
+
+        Self {
+            squares: vec![0.0; window_len],
+            position: 0,
+            sum: 0.0,
+            attack_coeff: smoothing_coeff(config.attack_ms, sample_rate),
+            release_coeff: smoothing_coeff(config.release_ms, sample_rate),
+            envelope: 0.0,
+        }
+    }
+
+    /// Feeds a single normalized sample into the detector.
+    pub fn process(&mut self, sample: f32) {
+        let square = sample * sample;
+        self.sum += (square - self.squares[self.position]) as f64;
+        self.squares[self.position] = square;
+        self.position += 1;
+
+        // recompute the sum once per window so floating point error can't pile up
+        if self.position == self.squares.len() {
+            self.position = 0;
+            self.sum = self.squares.iter().map(|&s| s as f64).sum();
+        }
+
+        let rms = (self.sum.max(0.0) / self.squares.len() as f64).sqrt() as f32;
+        let coeff = if rms > self.envelope {
+            self.attack_coeff
+        } else {
+            self.release_coeff
+        };
+        self.envelope = rms + coeff * (self.envelope - rms);
+    }
+
+    /// Returns the smoothed level, in dBFS.
+    pub fn level_dbfs(&self) -> f32 {
+        amplitude_to_dbfs(self.envelope)
+    }
+}
+
+/// Converts a linear amplitude, where 1.0 is full scale, to dBFS.
+pub fn amplitude_to_dbfs(amplitude: f32) -> f32 {
+    20.0 * amplitude.log10()
+}
+
+fn ms_to_samples(ms: f32, sample_rate: u32) -> f32 {
+    ms / 1000.0 * sample_rate as f32
+}
+
+/// Returns the coefficient of a one-pole filter that settles within the given time.
+fn smoothing_coeff(ms: f32, sample_rate: u32) -> f32 {
+    let samples = ms_to_samples(ms, sample_rate);
+    if samples <= 0.0 {
+        0.0
+    } else {
+        (-1.0 / samples).exp()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    #[test]
+    fn full_scale_sine_settles_at_minus_3_dbfs() {
+        let mut detector = LevelDetector::new(&LevelConfig::default(), SAMPLE_RATE);
+        for i in 0..SAMPLE_RATE {
+            detector.process((TAU * 1000.0 * i as f32 / SAMPLE_RATE as f32).sin());
+        }
+        assert!((detector.level_dbfs() + 3.01).abs() < 0.1);
+    }
+
+    #[test]
+    fn single_click_is_limited_by_the_attack() {
+        let config = LevelConfig::default();
+        let mut detector = LevelDetector::new(&config, SAMPLE_RATE);
+
+        // the click stays in the window for the window's length, during which the envelope can
+        // only rise part of the way towards the window's RMS
+        let window_len = detector.squares.len();
+        let click_rms = (1.0 / window_len as f32).sqrt();
+        let limit = click_rms * (1.0 - detector.attack_coeff.powi(window_len as i32));
+
+        let mut loudest = f32::NEG_INFINITY;
+        for i in 0..SAMPLE_RATE / 10 {
+            detector.process(if i == 100 { 1.0 } else { 0.0 });
+            loudest = loudest.max(detector.level_dbfs());
+        }
+        assert!(loudest <= amplitude_to_dbfs(limit) + 0.01);
+        assert!(loudest < -30.0);
+    }
+
+    #[test]
+    fn release_decays_at_the_configured_time_constant() {
+        let config = LevelConfig {
+            window_ms: 0.0,
+            attack_ms: 0.0,
+            release_ms: 150.0,
+        };
+        let mut detector = LevelDetector::new(&config, SAMPLE_RATE);
+        detector.process(1.0);
+        assert_eq!(detector.level_dbfs(), 0.0);
+
+        // after one time constant of silence, the level has fallen to 1/e of where it was
+        for _ in 0..ms_to_samples(config.release_ms, SAMPLE_RATE) as usize {
+            detector.process(0.0);
+        }
+        let expected = amplitude_to_dbfs((-1.0f32).exp());
+        assert!((detector.level_dbfs() - expected).abs() < 0.1);
+    }
+}
@@ -0,0 +1,153 @@
+use serde::Deserialize;
+
+use crate::threshold::Threshold;
+
+/// How far below an intensity threshold the intensity must fall for the mouth to close again.
+const THRESHOLD_HYSTERESIS: f32 = 0.08;
+
+/// Settings for gain normalization.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NormalizerConfig {
+    /// Whether to normalize the level at all. Without it, the mouth follows the level in dBFS,
+    /// so how wide it opens depends on the mic's gain.
+    pub enabled: bool,
+
+    /// The speech level to assume before any speech has been heard, in dBFS.
+    pub initial_speech_dbfs: f32,
+
+    /// How long the normalizer takes to get used to a new speech level, in seconds.
+    pub adapt_secs: f32,
+
+    /// Speech quieter than this never counts towards the speech level, in dBFS. This keeps
+    /// silence from dragging the speech level down when voice activity detection is disabled.
+    pub min_speech_dbfs: f32,
+
+    /// How far below the speech level the intensity reaches 0, in dB.
+    pub range_below_db: f32,
+
+    /// How far above the speech level the intensity reaches 1, in dB.
+    pub range_above_db: f32,
+
+    /// The intensities at which the mouth changes shape.
+    pub thresholds: IntensityThresholds,
+}
+
+impl Default for NormalizerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            initial_speech_dbfs: -30.0,
+            adapt_secs: 8.0,
+            min_speech_dbfs: -60.0,
+            range_below_db: 18.0,
+            range_above_db: 12.0,
+            thresholds: Default::default(),
+        }
+    }
+}
+
+/// The speech intensities at which the character's mouth changes shape, used in place of
+/// `SpeakThresholds` while gain normalization is enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IntensityThresholds {
+    /// The intensity at which the character is considered to be half speaking.
+    pub half_speak: Threshold,
+
+    /// The intensity at which the character is considered to be fully speaking.
+    pub full_speak: Threshold,
+
+    /// The intensity at which the character is considered to be yelling.
+    pub yell: Threshold,
+}
+
+impl Default for IntensityThresholds {
+    fn default() -> Self {
+        Self {
+            half_speak: Threshold::with_hysteresis(0.2, THRESHOLD_HYSTERESIS),
+            full_speak: Threshold::with_hysteresis(0.55, THRESHOLD_HYSTERESIS),
+            yell: Threshold::with_hysteresis(0.95, THRESHOLD_HYSTERESIS),
+        }
+    }
+}
+
+/// Tracks how loud the speaker usually talks and maps the level to a speech intensity from 0 to 1
+/// relative to that, so that quiet and loud voices get the same range of mouth shapes.
+pub struct GainNormalizer {
+    config: NormalizerConfig,
+
+    /// The speaker's recent speech level, in dBFS.
+    speech_dbfs: f32,
+}
+
+impl GainNormalizer {
+    pub fn new(config: &NormalizerConfig) -> Self {
+        Self {
+            config: config.clone(),
+            speech_dbfs: config.initial_speech_dbfs,
+        }
+    }
+
+    /// Updates the speech level with a block of `secs` seconds at the given level and returns the
+    /// block's speech intensity.
+    pub fn process(&mut self, level_dbfs: f32, is_speech: bool, secs: f32) -> f32 {
+        if is_speech && level_dbfs > self.config.min_speech_dbfs {
+            let coeff = 1.0 - (-secs / self.config.adapt_secs.max(f32::EPSILON)).exp();
+            self.speech_dbfs += (level_dbfs - self.speech_dbfs) * coeff;
+        }
+
+        let floor_dbfs = self.speech_dbfs - self.config.range_below_db;
+        let range_db = self.config.range_below_db + self.config.range_above_db;
+        ((level_dbfs - floor_dbfs) / range_db.max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalizer() -> GainNormalizer {
+        GainNormalizer::new(&NormalizerConfig {
+            enabled: true,
+            adapt_secs: 1.0,
+            ..Default::default()
+        })
+    }
+
+    /// Feeds the normalizer `secs` seconds of the given level in 10 ms blocks.
+    fn feed(normalizer: &mut GainNormalizer, level_dbfs: f32, is_speech: bool, secs: f32) {
+        for _ in 0..(secs * 100.0) as usize {
+            normalizer.process(level_dbfs, is_speech, 0.01);
+        }
+    }
+
+    /// The intensity of the usual speech level, 18 dB above the floor of a 30 dB range.
+    const USUAL: f32 = 0.6;
+
+    #[test]
+    fn adapts_to_a_new_speech_level() {
+        let mut normalizer = normalizer();
+        assert!((normalizer.process(-30.0, false, 0.01) - USUAL).abs() < 1e-3);
+
+        // after talking louder for a while, the louder level is the usual one
+        feed(&mut normalizer, -10.0, true, 10.0);
+        assert!((normalizer.process(-10.0, false, 0.01) - USUAL).abs() < 1e-3);
+        assert!(normalizer.process(-30.0, false, 0.01) < 0.1);
+    }
+
+    #[test]
+    fn quiet_levels_and_non_speech_are_ignored() {
+        let mut normalizer = normalizer();
+        feed(&mut normalizer, -70.0, true, 10.0);
+        feed(&mut normalizer, -10.0, false, 10.0);
+        assert!((normalizer.process(-30.0, false, 0.01) - USUAL).abs() < 1e-3);
+    }
+
+    #[test]
+    fn intensity_stays_between_0_and_1() {
+        let mut normalizer = normalizer();
+        assert_eq!(normalizer.process(-100.0, false, 0.01), 0.0);
+        assert_eq!(normalizer.process(0.0, false, 0.01), 1.0);
+    }
+}
@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use super::{
+    AudioConfig,
+    channels::ChannelMixer,
+    level::{self, LevelDetector},
+    normalizer::GainNormalizer,
+    pitch::PitchDetector,
+    spectrum::SpectrumAnalyzer,
+    transport::AnalysisFrame,
+    vad::VoiceActivityDetector,
+    viseme::{Viseme, VisemeClassifier},
+};
+
+/// The approximate duration of a frame for spectral analysis, in seconds. The actual frame length
+/// is rounded up to a power of two samples.
+const TARGET_FRAME_SECS: f32 = 0.02;
+
+/// Turns raw input samples into analysis frames for the rest of the app. Processing doesn't block
+/// or allocate, so it's safe to run in the input callback.
+pub struct Pipeline {
+    /// The number of samples per second, per channel.
+    sample_rate: u32,
+
+    /// The number of samples processed so far, per channel.
+    position: u64,
+
+    /// Mixes the selected channels down to the mono signal that gets analyzed.
+    mixer: ChannelMixer,
+
+    detector: LevelDetector,
+    normalizer: Option<GainNormalizer>,
+
+    /// Mono samples of the frame being collected for spectral analysis.
+    frame: Vec<f32>,
+
+    /// The number of samples in a full frame.
+    frame_len: usize,
+
+    spectrum: SpectrumAnalyzer,
+    vad: Option<VoiceActivityDetector>,
+    visemes: Option<VisemeClassifier>,
+    pitch: Option<PitchDetector>,
+
+    /// The results of the last spectral analysis.
+    is_speech: bool,
+    viseme: Option<Viseme>,
+    pitch_hz: Option<f32>,
+}
+
+impl Pipeline {
+    pub fn new(config: &AudioConfig, sample_rate: u32, channels: u16) -> Self {
+        let frame_len = ((sample_rate as f32 * TARGET_FRAME_SECS) as usize).next_power_of_two();
+        let frame_secs = frame_len as f32 / sample_rate as f32;
+
+        Self {
+            sample_rate,
+            position: 0,
+            mixer: ChannelMixer::new(channels, &config.channels, config.mix),
+            detector: LevelDetector::new(&config.level, sample_rate),
+            normalizer: config
+                .normalizer
+                .enabled
+                .then(|| GainNormalizer::new(&config.normalizer)),
+            frame: Vec::with_capacity(frame_len),
+            frame_len,
+            spectrum: SpectrumAnalyzer::new(frame_len, sample_rate),
+            vad: config
+                .vad
+                .enabled
+                .then(|| VoiceActivityDetector::new(&config.vad, frame_secs)),
+            visemes: config
+                .visemes
+                .enabled
+                .then(|| VisemeClassifier::new(&config.visemes, frame_len, sample_rate)),
+            pitch: config
+                .pitch
+                .enabled
+                .then(|| PitchDetector::new(&config.pitch, frame_len, sample_rate)),
+            is_speech: true,
+            viseme: None,
+            pitch_hz: None,
+        }
+    }
+
+    /// Analyzes a block of normalized, interleaved samples.
+    pub fn process(&mut self, samples: impl Iterator<Item = f32>) -> AnalysisFrame {
+        let mut peak: f32 = 0.0;
+        let start = self.position;
+
+        for sample in samples {
+            let Some(sample) = self.mixer.push(sample) else {
+                continue;
+            };
+
+            self.detector.process(sample);
+            peak = peak.max(sample.abs());
+            self.push_frame_sample(sample);
+            self.position += 1;
+        }
+
+        let level_dbfs = self.detector.level_dbfs();
+        let block_secs = (self.position - start) as f32 / self.sample_rate as f32;
+        let intensity = self
+            .normalizer
+            .as_mut()
+            .map(|normalizer| normalizer.process(level_dbfs, self.is_speech, block_secs));
+
+        AnalysisFrame {
+            time: Duration::from_secs_f64(self.position as f64 / self.sample_rate as f64),
+            level_dbfs,
+            intensity,
+            peak_dbfs: level::amplitude_to_dbfs(peak),
+            channel_levels: self.mixer.take_levels(),
+            is_speech: self.is_speech,
+            viseme: self.viseme,
+            pitch_hz: self.pitch_hz,
+        }
+    }
+
+    /// Adds a mono sample to the current frame, analyzing the frame once it's full.
+    fn push_frame_sample(&mut self, sample: f32) {
+        self.frame.push(sample);
+        if self.frame.len() < self.frame_len {
+            return;
+        }
+
+        let spectrum = self.spectrum.analyze(&self.frame);
+        self.is_speech = match &mut self.vad {
+            Some(vad) => vad.process(&self.frame, spectrum),
+            None => true,
+        };
+
+        if let Some(visemes) = &mut self.visemes {
+            self.viseme = visemes.process(&self.frame, spectrum, self.is_speech);
+        }
+
+        if let Some(pitch) = &mut self.pitch {
+            self.pitch_hz = pitch.process(&self.frame, self.is_speech);
+        }
+
+        self.frame.clear();
+    }
+}
@@ -0,0 +1,182 @@
+use serde::Deserialize;
+
+/// The sample rate pitch is estimated at, in Hz. Frames are decimated to roughly this rate, which
+/// is plenty for the fundamental of a voice and keeps the search cheap.
+const PITCH_SAMPLE_RATE: f32 = 11025.0;
+
+/// The number of frames of history the pitch is estimated over. Two frames cover a couple of
+/// periods of even a low voice.
+const HISTORY_FRAMES: usize = 2;
+
+/// Settings for pitch tracking.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PitchConfig {
+    /// Whether to track pitch at all.
+    pub enabled: bool,
+
+    /// The lowest pitch that can be detected, in Hz.
+    pub min_hz: f32,
+
+    /// The highest pitch that can be detected, in Hz.
+    pub max_hz: f32,
+
+    /// How aperiodic a frame may be and still have its pitch detected, from 0 to 1. Lower values
+    /// only accept clearly voiced frames.
+    pub threshold: f32,
+}
+
+impl Default for PitchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_hz: 70.0,
+            max_hz: 500.0,
+            threshold: 0.15,
+        }
+    }
+}
+
+/// Estimates the fundamental frequency of the voice using the YIN algorithm. All buffers are
+/// allocated up front, so processing a frame doesn't allocate.
+pub struct PitchDetector {
+    threshold: f32,
+
+    /// How many input samples are averaged into each decimated sample.
+    decimation: usize,
+
+    /// The sample rate after decimation, in Hz.
+    sample_rate: f32,
+
+    /// The most recent decimated samples, oldest first.
+    history: Vec<f32>,
+
+    /// The number of samples each difference is summed over.
+    window_len: usize,
+
+    /// The shortest and longest periods searched, in decimated samples.
+    min_period: usize,
+    max_period: usize,
+
+    /// The cumulative mean normalized difference for each period.
+    differences: Vec<f32>,
+}
+
+impl PitchDetector {
+    pub fn new(config: &PitchConfig, frame_len: usize, sample_rate: u32) -> Self {
+        let decimation = ((sample_rate as f32 / PITCH_SAMPLE_RATE) as usize).max(1);
+        let decimated_rate = sample_rate as f32 / decimation as f32;
+        let history_len = frame_len / decimation * HISTORY_FRAMES;
+
+        // keep the search range within the history, even for pitches too low to fit in it
+        let min_period = ((decimated_rate / config.max_hz.max(1.0)) as usize)
+            .max(2)
+            .min(history_len / 2 - 1);
+        let max_period = ((decimated_rate / config.min_hz.max(1.0)).ceil() as usize)
+            .clamp(min_period + 1, history_len / 2);
+
+        Self {
+            threshold: config.threshold,
+            decimation,
+            sample_rate: decimated_rate,
+            history: vec![0.0; history_len],
+            window_len: history_len - max_period - 1,
+            min_period,
+            max_period,
+            differences: vec![0.0; max_period + 2],
+        }
+    }
+
+    /// Adds the next frame and returns the pitch over the recent history in Hz, or `None` if the
+    /// input isn't clearly pitched. `is_voiced` should be `false` when the frame isn't speech, in
+    /// which case no pitch is reported.
+    pub fn process(&mut self, frame: &[f32], is_voiced: bool) -> Option<f32> {
+        let new_len = frame.len() / self.decimation;
+        self.history.rotate_left(new_len);
+        let start = self.history.len() - new_len;
+        for (sample, chunk) in self.history[start..]
+            .iter_mut()
+            .zip(frame.chunks_exact(self.decimation))
+        {
+            *sample = chunk.iter().sum::<f32>() / self.decimation as f32;
+        }
+
+        if is_voiced { self.estimate() } else { None }
+    }
+
+    fn estimate(&mut self) -> Option<f32> {
+        let x = &self.history;
+        let w = self.window_len;
+
+        // cumulative mean normalized difference function
+        self.differences[0] = 1.0;
+        let mut running_sum = 0.0;
+        for period in 1..=self.max_period + 1 {
+            let difference: f32 = (0..w).map(|j| (x[j] - x[j + period]).powi(2)).sum();
+            running_sum += difference;
+            self.differences[period] = if running_sum > 0.0 {
+                difference * period as f32 / running_sum
+            } else {
+                1.0
+            };
+        }
+
+        // the first dip below the threshold, followed down to its bottom
+        let d = &self.differences;
+        let mut period = (self.min_period..=self.max_period).find(|&p| d[p] < self.threshold)?;
+        while period < self.max_period && d[period + 1] < d[period] {
+            period += 1;
+        }
+
+        // refine the period between samples with a parabola through its neighbours
+        let (before, at, after) = (d[period - 1], d[period], d[period + 1]);
+        let curvature = before - 2.0 * at + after;
+        let offset = if curvature > 0.0 {
+            (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.0
+        };
+
+        Some(self.sample_rate / (period as f32 + offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+    const FRAME_LEN: usize = 1024;
+
+    #[test]
+    fn tone_pitch_is_detected() {
+        let mut detector = PitchDetector::new(&PitchConfig::default(), FRAME_LEN, SAMPLE_RATE);
+        let mut pitch = None;
+        for index in 0..4 {
+            let frame: Vec<f32> = (0..FRAME_LEN)
+                .map(|i| {
+                    let t = (index * FRAME_LEN + i) as f32 / SAMPLE_RATE as f32;
+                    (TAU * 200.0 * t).sin() * 0.5
+                })
+                .collect();
+            pitch = detector.process(&frame, true);
+        }
+        let pitch = pitch.unwrap();
+        assert!((pitch - 200.0).abs() < 5.0, "detected {pitch} Hz");
+    }
+
+    #[test]
+    fn pitch_range_below_the_history_does_not_panic() {
+        for (min_hz, max_hz) in [(10.0, 20.0), (500.0, 70.0), (0.0, 0.0)] {
+            let config = PitchConfig {
+                min_hz,
+                max_hz,
+                ..Default::default()
+            };
+            let mut detector = PitchDetector::new(&config, FRAME_LEN, SAMPLE_RATE);
+            detector.process(&[0.1; FRAME_LEN], true);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::{f32::consts::TAU, sync::Arc};
+
+use realfft::{RealFftPlanner, RealToComplex, num_complex::Complex};
+
+/// Computes magnitude spectra of fixed-size frames. All buffers are allocated up front, so
+/// analyzing a frame doesn't allocate and is safe to do on the audio thread.
+pub struct SpectrumAnalyzer {
+    fft: Arc<dyn RealToComplex<f32>>,
+
+    /// A Hann window, applied to each frame before the transform.
+    window: Vec<f32>,
+
+    input: Vec<f32>,
+    output: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+
+    /// The magnitude of each frequency bin of the last analyzed frame.
+    magnitudes: Vec<f32>,
+
+    /// The width of each frequency bin, in Hz.
+    bin_hz: f32,
+}
+
+impl SpectrumAnalyzer {
+    pub fn new(frame_len: usize, sample_rate: u32) -> Self {
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(frame_len);
+        let window = (0..frame_len)
+            .map(|i| 0.5 - 0.5 * (TAU * i as f32 / frame_len as f32).cos())
+            .collect();
+
+        Self {
+            input: fft.make_input_vec(),
+            output: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            magnitudes: vec![0.0; frame_len / 2 + 1],
+            bin_hz: sample_rate as f32 / frame_len as f32,
+            window,
+            fft,
+        }
+    }
+
+    /// Computes the magnitude spectrum of the given frame, which must be as long as the frame
+    /// length given to `new`.
+    pub fn analyze(&mut self, frame: &[f32]) -> Spectrum<'_> {
+        for ((input, sample), window) in self.input.iter_mut().zip(frame).zip(&self.window) {
+            *input = sample * window;
+        }
+
+        // the buffers are sized by the planner itself, so this can't fail
+        let _ = self
+            .fft
+            .process_with_scratch(&mut self.input, &mut self.output, &mut self.scratch);
+
+        for (magnitude, bin) in self.magnitudes.iter_mut().zip(&self.output) {
+            *magnitude = bin.norm();
+        }
+
+        Spectrum {
+            magnitudes: &self.magnitudes,
+            bin_hz: self.bin_hz,
+        }
+    }
+}
+
+/// The magnitude spectrum of a frame.
+#[derive(Clone, Copy)]
+pub struct Spectrum<'a> {
+    pub magnitudes: &'a [f32],
+
+    /// The width of each frequency bin, in Hz.
+    pub bin_hz: f32,
+}
+
+impl Spectrum<'_> {
+    /// Returns the magnitudes of the bins between the given frequencies, in Hz.
+    pub fn band(&self, low_hz: f32, high_hz: f32) -> &[f32] {
+        let len = self.magnitudes.len();
+        let low = ((low_hz / self.bin_hz).ceil() as usize).min(len);
+        let high = ((high_hz / self.bin_hz).floor() as usize)
+            .saturating_add(1)
+            .clamp(low, len);
+        &self.magnitudes[low..high]
+    }
+}
+
+/// Returns the total energy of the given bins.
+pub fn energy(bins: &[f32]) -> f32 {
+    bins.iter().map(|m| m * m).sum()
+}
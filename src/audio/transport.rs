@@ -0,0 +1,137 @@
+use std::{sync::mpsc, time::Duration};
+
+use rtrb::{Consumer, Producer, RingBuffer};
+
+use super::{SILENCE_DBFS, Viseme, channels::ChannelLevels};
+
+/// The number of analysis frames buffered between the audio thread and the UI. Input callbacks
+/// usually run every few milliseconds, so this holds a couple of seconds of frames, while the UI
+/// drains the queue every frame.
+const FRAME_QUEUE_CAPACITY: usize = 512;
+
+/// The result of analyzing one block of input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnalysisFrame {
+    /// The position of the end of the block in the stream.
+    pub time: Duration,
+
+    /// The smoothed RMS level, in dBFS.
+    pub level_dbfs: f32,
+
+    /// How loud the block is relative to the speaker's usual speech level, from 0 to 1, or `None`
+    /// if gain normalization is disabled.
+    pub intensity: Option<f32>,
+
+    /// The highest sample in the block, in dBFS.
+    pub peak_dbfs: f32,
+
+    /// The peak level of every channel of the input in the block, including channels that don't
+    /// feed the character.
+    pub channel_levels: ChannelLevels,
+
+    /// Whether the input sounds like speech. Always `true` if voice activity detection is
+    /// disabled.
+    pub is_speech: bool,
+
+    /// The mouth shape recognized in the input, or `None` if no shape was recognized or viseme
+    /// recognition is disabled.
+    pub viseme: Option<Viseme>,
+
+    /// The pitch of the voice in Hz, or `None` if the input isn't clearly pitched or pitch
+    /// tracking is disabled.
+    pub pitch_hz: Option<f32>,
+}
+
+impl AnalysisFrame {
+    /// A frame of silence, reported while no input is attached.
+    pub const SILENT: Self = Self {
+        time: Duration::ZERO,
+        level_dbfs: SILENCE_DBFS,
+        intensity: None,
+        peak_dbfs: SILENCE_DBFS,
+        channel_levels: ChannelLevels::EMPTY,
+        is_speech: false,
+        viseme: None,
+        pitch_hz: None,
+    };
+}
+
+/// The audio thread's end of the frame queue. Sending never blocks or allocates; if the UI falls
+/// so far behind that the queue is full, new frames are dropped.
+pub struct FrameSender {
+    producer: Producer<AnalysisFrame>,
+}
+
+impl FrameSender {
+    pub fn send(&mut self, frame: AnalysisFrame) {
+        let _ = self.producer.push(frame);
+    }
+
+    /// Returns whether the UI has fallen behind far enough that new frames would be dropped.
+    pub fn is_full(&self) -> bool {
+        self.producer.is_full()
+    }
+}
+
+/// The UI's end of a frame queue.
+pub struct FrameQueue {
+    consumer: Consumer<AnalysisFrame>,
+}
+
+/// Creates a frame queue for a new input stream.
+pub fn frame_queue() -> (FrameSender, FrameQueue) {
+    let (producer, consumer) = RingBuffer::new(FRAME_QUEUE_CAPACITY);
+    (FrameSender { producer }, FrameQueue { consumer })
+}
+
+/// Hands the queues of new input streams over to the UI.
+pub struct QueueHandover {
+    new_queues: mpsc::Sender<FrameQueue>,
+}
+
+impl QueueHandover {
+    /// Makes the UI receive frames from the given queue from now on.
+    pub fn hand_over(&self, queue: FrameQueue) {
+        let _ = self.new_queues.send(queue);
+    }
+}
+
+/// The UI's end of the frame queues. Every input stream gets its own queue, which is handed over
+/// once the stream has started.
+pub struct FrameReceiver {
+    new_queues: mpsc::Receiver<FrameQueue>,
+    queue: Option<FrameQueue>,
+}
+
+impl FrameReceiver {
+    /// Returns every frame received since the last call, oldest first.
+    pub fn receive(&mut self) -> Vec<AnalysisFrame> {
+        let mut frames = Vec::new();
+
+        while let Ok(new_queue) = self.new_queues.try_recv() {
+            // whatever the old stream sent before it stopped still counts
+            if let Some(old_queue) = &mut self.queue {
+                frames.extend(std::iter::from_fn(|| old_queue.consumer.pop().ok()));
+            }
+            self.queue = Some(new_queue);
+        }
+
+        if let Some(queue) = &mut self.queue {
+            frames.extend(std::iter::from_fn(|| queue.consumer.pop().ok()));
+        }
+
+        frames
+    }
+}
+
+/// Creates a connected queue handover and receiver.
+pub fn frame_channel() -> (QueueHandover, FrameReceiver) {
+    let (sender, receiver) = mpsc::channel();
+    (
+        QueueHandover { new_queues: sender },
+        FrameReceiver {
+            new_queues: receiver,
+            queue: None,
+        },
+    )
+}
@@ -0,0 +1,260 @@
+use serde::Deserialize;
+
+use super::spectrum::{self, Spectrum};
+
+/// The lower edge of the band most of a voice's energy lies in, in Hz.
+const VOICE_BAND_LOW_HZ: f32 = 300.0;
+
+/// The upper edge of the band most of a voice's energy lies in, in Hz.
+const VOICE_BAND_HIGH_HZ: f32 = 3400.0;
+
+/// Frames with less total energy than this are treated as silence rather than analyzed.
+const MIN_FRAME_ENERGY: f32 = 1e-9;
+
+/// Settings for voice activity detection.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VadConfig {
+    /// Whether to use voice activity detection at all. Without it, any sound that's loud enough
+    /// counts as speech.
+    pub enabled: bool,
+
+    /// The smallest share of a frame's energy that must lie in the voice band for the frame to
+    /// count as speech.
+    pub min_voice_band_ratio: f32,
+
+    /// The highest spectral flatness of the voice band that counts as speech. Noise and clicks
+    /// have a flat spectrum, while voices have distinct harmonics.
+    pub max_flatness: f32,
+
+    /// The highest share of samples at which the signal may cross zero for a frame to count as
+    /// speech. Clicks cross zero far more often than voiced speech does.
+    pub max_zero_crossing_rate: f32,
+
+    /// How long a sound must keep looking like speech before it's detected as speech, in
+    /// milliseconds. This keeps short clicks out.
+    pub min_speech_ms: f32,
+
+    /// How long speech is still reported after the last frame that looked like speech, in
+    /// milliseconds. This bridges consonants and short pauses between words.
+    pub hangover_ms: f32,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_voice_band_ratio: 0.5,
+            max_flatness: 0.4,
+            max_zero_crossing_rate: 0.25,
+            min_speech_ms: 50.0,
+            hangover_ms: 250.0,
+        }
+    }
+}
+
+/// Spectral and temporal features of a single frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VadFeatures {
+    /// The share of the frame's energy that lies in the voice band, from 0 to 1.
+    pub voice_band_ratio: f32,
+
+    /// The spectral flatness of the voice band, from 0 (a pure tone) to 1 (white noise).
+    pub flatness: f32,
+
+    /// The share of samples at which the signal crosses zero, from 0 to 1.
+    pub zero_crossing_rate: f32,
+}
+
+impl VadFeatures {
+    /// Measures the features of a frame, given its samples and its spectrum. Returns `None` if the
+    /// frame is too quiet to say anything about.
+    pub fn measure(frame: &[f32], spectrum: Spectrum<'_>) -> Option<Self> {
+        let total_energy = spectrum::energy(spectrum.magnitudes);
+        if total_energy < MIN_FRAME_ENERGY {
+            return None;
+        }
+
+        let voice_band = spectrum.band(VOICE_BAND_LOW_HZ, VOICE_BAND_HIGH_HZ);
+
+        Some(Self {
+            voice_band_ratio: spectrum::energy(voice_band) / total_energy,
+            flatness: spectral_flatness(voice_band),
+            zero_crossing_rate: zero_crossing_rate(frame),
+        })
+    }
+
+    /// Returns whether these features look like speech under the given settings.
+    pub fn looks_like_speech(&self, config: &VadConfig) -> bool {
+        self.voice_band_ratio >= config.min_voice_band_ratio
+            && self.flatness <= config.max_flatness
+            && self.zero_crossing_rate <= config.max_zero_crossing_rate
+    }
+}
+
+/// Decides, frame by frame, whether the input is speech or some other sound such as typing.
+pub struct VoiceActivityDetector {
+    config: VadConfig,
+
+    /// How many consecutive frames must look like speech before speech is detected.
+    min_speech_frames: u32,
+
+    /// How many frames speech is still reported for after the last speech-like frame.
+    hangover_frames: u32,
+
+    /// How many consecutive frames have looked like speech.
+    speech_frames: u32,
+
+    /// How many frames are left before speech stops being reported.
+    hangover_left: u32,
+}
+
+impl VoiceActivityDetector {
+    /// Creates a detector for frames that each last `frame_secs` seconds.
+    pub fn new(config: &VadConfig, frame_secs: f32) -> Self {
+        let frames = |ms: f32| (ms / 1000.0 / frame_secs).ceil().max(0.0) as u32;
+
+        Self {
+            config: config.clone(),
+            min_speech_frames: frames(config.min_speech_ms).max(1),
+            hangover_frames: frames(config.hangover_ms),
+            speech_frames: 0,
+            hangover_left: 0,
+        }
+    }
+
+    /// Analyzes the next frame and returns whether the input is currently speech.
+    pub fn process(&mut self, frame: &[f32], spectrum: Spectrum<'_>) -> bool {
+        let looks_like_speech = VadFeatures::measure(frame, spectrum)
+            .is_some_and(|features| features.looks_like_speech(&self.config));
+
+        if looks_like_speech {
+            self.speech_frames = self.speech_frames.saturating_add(1);
+        } else {
+            self.speech_frames = 0;
+        }
+
+        if self.speech_frames >= self.min_speech_frames {
+            self.hangover_left = self.hangover_frames + 1;
+        }
+        self.hangover_left = self.hangover_left.saturating_sub(1);
+
+        self.speech_frames >= self.min_speech_frames || self.hangover_left > 0
+    }
+}
+
+/// Returns the spectral flatness of the given bins: the geometric mean of their energies divided
+/// by the arithmetic mean.
+fn spectral_flatness(bins: &[f32]) -> f32 {
+    if bins.is_empty() {
+        return 1.0;
+    }
+
+    let n = bins.len() as f32;
+    let arithmetic_mean = spectrum::energy(bins) / n;
+    if arithmetic_mean <= 0.0 {
+        return 1.0;
+    }
+
+    let log_sum: f32 = bins
+        .iter()
+        .map(|m| (m * m).max(f32::MIN_POSITIVE).ln())
+        .sum();
+    (log_sum / n).exp() / arithmetic_mean
+}
+
+/// Returns the share of adjacent samples between which the signal changes sign.
+fn zero_crossing_rate(frame: &[f32]) -> f32 {
+    if frame.len() < 2 {
+        return 0.0;
+    }
+
+    let crossings = frame
+        .windows(2)
+        .filter(|pair| (pair[0] >= 0.0) != (pair[1] >= 0.0))
+        .count();
+    crossings as f32 / (frame.len() - 1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::*;
+    use crate::audio::spectrum::SpectrumAnalyzer;
+
+    const SAMPLE_RATE: u32 = 48000;
+    const FRAME_LEN: usize = 1024;
+
+    /// Returns a frame of a vowel-like tone: a 200 Hz fundamental with its harmonics up to about
+    /// 2 kHz, strongest around 600 Hz.
+    fn voiced_frame(index: usize) -> Vec<f32> {
+        (0..FRAME_LEN)
+            .map(|i| {
+                let t = (index * FRAME_LEN + i) as f32 / SAMPLE_RATE as f32;
+                [0.2, 0.6, 1.0, 0.7, 0.4, 0.3, 0.2, 0.1, 0.05, 0.05]
+                    .iter()
+                    .enumerate()
+                    .map(|(k, amplitude)| amplitude * (TAU * 200.0 * (k + 1) as f32 * t).sin())
+                    .sum::<f32>()
+                    * 0.1
+            })
+            .collect()
+    }
+
+    /// Returns a silent frame with a single click in the middle.
+    fn click_frame() -> Vec<f32> {
+        let mut frame = vec![0.0; FRAME_LEN];
+        frame[FRAME_LEN / 2] = 0.8;
+        frame
+    }
+
+    /// Returns a frame of white noise.
+    fn noise_frame() -> Vec<f32> {
+        (0..FRAME_LEN)
+            .map(|_| rand::random::<f32>() * 0.4 - 0.2)
+            .collect()
+    }
+
+    /// Runs the given frames through a detector with the default settings, returning whether
+    /// each one was detected as speech.
+    fn detect(frames: impl IntoIterator<Item = Vec<f32>>) -> Vec<bool> {
+        let config = VadConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let mut vad = VoiceActivityDetector::new(&config, FRAME_LEN as f32 / SAMPLE_RATE as f32);
+        let mut spectrum = SpectrumAnalyzer::new(FRAME_LEN, SAMPLE_RATE);
+        frames
+            .into_iter()
+            .map(|frame| {
+                let analyzed = spectrum.analyze(&frame);
+                vad.process(&frame, analyzed)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn voiced_tone_is_speech() {
+        let is_speech = detect((0..10).map(voiced_frame));
+        assert!(!is_speech[0], "speech is detected only after min_speech_ms");
+        assert!(is_speech[5..].iter().all(|&s| s));
+    }
+
+    #[test]
+    fn click_is_not_speech() {
+        let frames = (0..10).map(|i| {
+            if i == 3 {
+                click_frame()
+            } else {
+                vec![0.0; FRAME_LEN]
+            }
+        });
+        assert!(detect(frames).iter().all(|&s| !s));
+    }
+
+    #[test]
+    fn noise_burst_is_not_speech() {
+        assert!(detect((0..10).map(|_| noise_frame())).iter().all(|&s| !s));
+    }
+}
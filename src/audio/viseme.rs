@@ -0,0 +1,278 @@
+use std::f32::consts::PI;
+
+use serde::Deserialize;
+
+use super::spectrum::{self, Spectrum};
+
+/// The sample rate formants are estimated at, in Hz. Frames are decimated to roughly this rate,
+/// which covers the first few formants while keeping the LPC order low.
+const FORMANT_SAMPLE_RATE: f32 = 11025.0;
+
+/// The order of the linear prediction model. Two poles per formant plus a couple for the glottal
+/// source and lip radiation.
+const LPC_ORDER: usize = 12;
+
+/// The number of points the LPC envelope is evaluated at between 0 Hz and Nyquist.
+const ENVELOPE_POINTS: usize = 256;
+
+/// Coefficient of the pre-emphasis filter, which flattens the natural tilt of voiced speech.
+const PRE_EMPHASIS: f32 = 0.97;
+
+/// Envelope peaks below this frequency are ignored when looking for formants, in Hz.
+const MIN_FORMANT_HZ: f32 = 200.0;
+
+/// The frequency above which a nasal murmur has almost no energy, in Hz.
+const MURMUR_CUTOFF_HZ: f32 = 1000.0;
+
+/// The highest first formant a nasal murmur has, in Hz. Back vowels like O are just as muffled
+/// but have a higher first formant.
+const MAX_MURMUR_F1_HZ: f32 = 350.0;
+
+/// Typical first and second formants of each vowel, in Hz.
+const VOWEL_FORMANTS: [(Viseme, f32, f32); 5] = [
+    (Viseme::A, 800.0, 1300.0),
+    (Viseme::I, 300.0, 2300.0),
+    (Viseme::U, 350.0, 1400.0),
+    (Viseme::E, 500.0, 1900.0),
+    (Viseme::O, 500.0, 900.0),
+];
+
+/// A mouth shape that can be recognized from the sound of the voice.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Viseme {
+    A,
+    I,
+    U,
+    E,
+    O,
+
+    /// Closed lips, as in M, B and P.
+    Closed,
+}
+
+/// Settings for viseme recognition.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VisemeConfig {
+    /// Whether to recognize visemes at all.
+    pub enabled: bool,
+
+    /// The largest share of a frame's energy that may lie above 1 kHz for the frame to count as a
+    /// closed-lip murmur (M, B, P) instead of a vowel.
+    pub max_murmur_ratio: f32,
+
+    /// How many consecutive frames must agree on a viseme before it's reported. Higher values
+    /// flicker less but react later.
+    pub min_stable_frames: u32,
+}
+
+impl Default for VisemeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_murmur_ratio: 0.02,
+            min_stable_frames: 2,
+        }
+    }
+}
+
+/// Recognizes visemes from the first two formants of the voice, estimated with linear prediction.
+/// All buffers are allocated up front, so classifying a frame doesn't allocate.
+pub struct VisemeClassifier {
+    config: VisemeConfig,
+
+    /// How many input samples are averaged into each decimated sample.
+    decimation: usize,
+
+    /// The sample rate after decimation, in Hz.
+    sample_rate: f32,
+
+    /// The decimated, pre-emphasized and windowed frame.
+    samples: Vec<f32>,
+
+    /// A Hamming window as long as `samples`.
+    window: Vec<f32>,
+
+    autocorrelation: [f32; LPC_ORDER + 1],
+    coeffs: [f32; LPC_ORDER + 1],
+    previous_coeffs: [f32; LPC_ORDER + 1],
+
+    /// `cos` and `sin` of `i * w` for each envelope point `w` and LPC coefficient `i`.
+    envelope_basis: Vec<[(f32, f32); LPC_ORDER + 1]>,
+    envelope: Vec<f32>,
+
+    /// The last viseme that was classified, and for how many consecutive frames.
+    candidate: Option<Viseme>,
+    candidate_frames: u32,
+
+    /// The viseme currently reported.
+    current: Option<Viseme>,
+}
+
+impl VisemeClassifier {
+    pub fn new(config: &VisemeConfig, frame_len: usize, sample_rate: u32) -> Self {
+        let decimation = ((sample_rate as f32 / FORMANT_SAMPLE_RATE) as usize).max(1);
+        let len = frame_len / decimation;
+
+        let window = (0..len)
+            .map(|i| 0.54 - 0.46 * (2.0 * PI * i as f32 / (len.max(2) - 1) as f32).cos())
+            .collect();
+
+        let envelope_basis = (0..ENVELOPE_POINTS)
+            .map(|k| {
+                let w = PI * k as f32 / ENVELOPE_POINTS as f32;
+                std::array::from_fn(|i| ((w * i as f32).cos(), (w * i as f32).sin()))
+            })
+            .collect();
+
+        Self {
+            config: config.clone(),
+            decimation,
+            sample_rate: sample_rate as f32 / decimation as f32,
+            samples: vec![0.0; len],
+            window,
+            autocorrelation: [0.0; LPC_ORDER + 1],
+            coeffs: [0.0; LPC_ORDER + 1],
+            previous_coeffs: [0.0; LPC_ORDER + 1],
+            envelope_basis,
+            envelope: vec![0.0; ENVELOPE_POINTS],
+            candidate: None,
+            candidate_frames: 0,
+            current: None,
+        }
+    }
+
+    /// Classifies the next frame and returns the viseme currently recognized. `is_voiced` should
+    /// be `false` when the frame isn't speech, in which case no viseme is recognized.
+    pub fn process(
+        &mut self,
+        frame: &[f32],
+        spectrum: Spectrum<'_>,
+        is_voiced: bool,
+    ) -> Option<Viseme> {
+        let viseme = if is_voiced {
+            self.classify(frame, spectrum)
+        } else {
+            None
+        };
+
+        if viseme == self.candidate {
+            self.candidate_frames = self.candidate_frames.saturating_add(1);
+        } else {
+            self.candidate = viseme;
+            self.candidate_frames = 1;
+        }
+
+        if viseme.is_none() || self.candidate_frames >= self.config.min_stable_frames {
+            self.current = viseme;
+        }
+        self.current
+    }
+
+    /// Classifies a single frame, without any smoothing.
+    fn classify(&mut self, frame: &[f32], spectrum: Spectrum<'_>) -> Option<Viseme> {
+        let total_energy = spectrum::energy(spectrum.magnitudes);
+        if total_energy <= 0.0 {
+            return None;
+        }
+
+        // a murmur through closed lips has a low first formant and almost nothing above it
+        let high_energy = spectrum::energy(spectrum.band(MURMUR_CUTOFF_HZ, f32::INFINITY));
+        let is_muffled = high_energy / total_energy <= self.config.max_murmur_ratio;
+        let formants = self.formants(frame);
+        if is_muffled && formants.is_none_or(|(f1, _)| f1 <= MAX_MURMUR_F1_HZ) {
+            return Some(Viseme::Closed);
+        }
+
+        let (f1, f2) = formants?;
+        VOWEL_FORMANTS
+            .iter()
+            .min_by(|a, b| {
+                formant_distance(f1, f2, a.1, a.2).total_cmp(&formant_distance(f1, f2, b.1, b.2))
+            })
+            .map(|&(viseme, _, _)| viseme)
+    }
+
+    /// Estimates the first two formants of the frame, in Hz.
+    fn formants(&mut self, frame: &[f32]) -> Option<(f32, f32)> {
+        // decimate by averaging, which doubles as a crude anti-aliasing filter, then pre-emphasize
+        // and window
+        let mut previous = 0.0;
+        for (i, sample) in self.samples.iter_mut().enumerate() {
+            let chunk = &frame[i * self.decimation..(i + 1) * self.decimation];
+            let decimated = chunk.iter().sum::<f32>() / self.decimation as f32;
+            *sample = (decimated - PRE_EMPHASIS * previous) * self.window[i];
+            previous = decimated;
+        }
+
+        for lag in 0..=LPC_ORDER {
+            self.autocorrelation[lag] = self
+                .samples
+                .iter()
+                .zip(self.samples.iter().skip(lag))
+                .map(|(a, b)| a * b)
+                .sum();
+        }
+        if !self.levinson_durbin() {
+            return None;
+        }
+
+        for (magnitude, basis) in self.envelope.iter_mut().zip(&self.envelope_basis) {
+            let (re, im) = self
+                .coeffs
+                .iter()
+                .zip(basis)
+                .fold((0.0, 0.0), |(re, im), (a, (cos, sin))| {
+                    (re + a * cos, im - a * sin)
+                });
+            *magnitude = 1.0 / (re * re + im * im).sqrt().max(f32::MIN_POSITIVE);
+        }
+
+        let hz_per_point = self.sample_rate / 2.0 / ENVELOPE_POINTS as f32;
+        let mut peaks = self
+            .envelope
+            .windows(3)
+            .enumerate()
+            .filter(|(_, w)| w[1] > w[0] && w[1] >= w[2])
+            .map(|(i, _)| (i + 1) as f32 * hz_per_point)
+            .filter(|&hz| hz >= MIN_FORMANT_HZ);
+
+        Some((peaks.next()?, peaks.next()?))
+    }
+
+    /// Solves for the LPC coefficients from the autocorrelation. Returns `false` if the frame is
+    /// silent or the solution is unstable.
+    fn levinson_durbin(&mut self) -> bool {
+        let r = &self.autocorrelation;
+        if r[0] <= 0.0 {
+            return false;
+        }
+
+        self.coeffs = [0.0; LPC_ORDER + 1];
+        self.coeffs[0] = 1.0;
+        let mut error = r[0];
+
+        for i in 1..=LPC_ORDER {
+            let acc: f32 = (1..i).map(|j| self.coeffs[j] * r[i - j]).sum();
+            let k = -(r[i] + acc) / error;
+            if !k.is_finite() || k.abs() >= 1.0 {
+                return false;
+            }
+
+            self.previous_coeffs = self.coeffs;
+            for j in 1..i {
+                self.coeffs[j] = self.previous_coeffs[j] + k * self.previous_coeffs[i - j];
+            }
+            self.coeffs[i] = k;
+            error *= 1.0 - k * k;
+        }
+
+        true
+    }
+}
+
+/// Returns how far apart two pairs of formants sound, comparing them on a logarithmic scale like
+/// the ear does.
+fn formant_distance(f1: f32, f2: f32, vowel_f1: f32, vowel_f2: f32) -> f32 {
+    (f1 / vowel_f1).ln().powi(2) + (f2 / vowel_f2).ln().powi(2)
+}
@@ -0,0 +1,224 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    head::{SpeakThresholds, THRESHOLD_HYSTERESIS_DB},
+    threshold::Threshold,
+};
+
+/// The name of the file calibration results are saved to, inside the app's config directory.
+const CALIBRATION_FILE_NAME: &str = "calibration.toml";
+
+/// The lowest noise floor a calibration can measure, in dBFS. Digital silence would otherwise
+/// come out as negative infinity.
+const MIN_NOISE_FLOOR_DBFS: f32 = -100.0;
+
+/// The percentile of levels heard during calibration that is taken as the noise floor. Using a
+/// high percentile rather than the average keeps the gate above most of the background noise.
+const NOISE_FLOOR_PERCENTILE: f32 = 0.95;
+
+/// The highest any threshold derived from a calibration can be, in dBFS.
+const MAX_THRESHOLD_DBFS: f32 = -1.0;
+
+/// Settings for noise floor calibration. The offsets decide where the noise gate and the speaking
+/// thresholds end up relative to the measured noise floor.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CalibrationConfig {
+    /// How long to listen for silence, in seconds.
+    pub duration_secs: f32,
+
+    /// How far above the noise floor the noise gate opens, in dB.
+    pub gate_margin_db: f32,
+
+    /// How far above the noise floor the character starts half speaking, in dB.
+    pub half_speak_offset_db: f32,
+
+    /// How far above the noise floor the character starts fully speaking, in dB.
+    pub full_speak_offset_db: f32,
+
+    /// How far above the noise floor the character starts yelling, in dB.
+    pub yell_offset_db: f32,
+
+    /// How far below each speaking threshold the level must fall for the mouth to close again,
+    /// in dB.
+    pub hysteresis_db: f32,
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        Self {
+            duration_secs: 3.0,
+            gate_margin_db: 6.0,
+            half_speak_offset_db: 12.0,
+            full_speak_offset_db: 26.0,
+            yell_offset_db: 48.0,
+            hysteresis_db: THRESHOLD_HYSTERESIS_DB,
+        }
+    }
+}
+
+/// The result of a calibration. This is saved so that it survives restarts.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Calibration {
+    /// The level of the background noise, in dBFS.
+    pub noise_floor_dbfs: f32,
+}
+
+impl Calibration {
+    /// Loads the saved calibration of the character with the given name and position in the
+    /// scene, if there is one.
+    pub fn load(character: Option<&str>, index: usize) -> Option<Self> {
+        let path = file_path(character, index)?;
+        match fs::read_to_string(&path) {
+            Ok(contents) => match toml::from_str(&contents) {
+                Ok(calibration) => Some(calibration),
+                Err(e) => {
+                    eprintln!("ignoring invalid calibration in {}: {e}", path.display());
+                    None
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                eprintln!("couldn't read calibration from {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    /// Saves the calibration so that it's used the next time the app starts.
+    pub fn save(&self, character: Option<&str>, index: usize) -> io::Result<PathBuf> {
+        let path = file_path(character, index)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        let contents = toml::to_string(self).map_err(io::Error::other)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, contents)?;
+
+        Ok(path)
+    }
+
+    /// Returns the level at which the noise gate should open, in dBFS.
+    pub fn gate_threshold_dbfs(&self, config: &CalibrationConfig) -> f32 {
+        self.offset(config.gate_margin_db)
+    }
+
+    /// Returns the speaking thresholds relative to the noise floor.
+    pub fn speak_thresholds(&self, config: &CalibrationConfig) -> SpeakThresholds {
+        SpeakThresholds {
+            half_speak_dbfs: self.threshold(config.half_speak_offset_db, config),
+            full_speak_dbfs: self.threshold(config.full_speak_offset_db, config),
+            yell_dbfs: self.threshold(config.yell_offset_db, config),
+        }
+    }
+
+    fn offset(&self, db: f32) -> f32 {
+        (self.noise_floor_dbfs + db).min(MAX_THRESHOLD_DBFS)
+    }
+
+    fn threshold(&self, db: f32, config: &CalibrationConfig) -> Threshold {
+        Threshold::with_hysteresis(self.offset(db), config.hysteresis_db)
+    }
+}
+
+/// Returns the path the calibration of the character with the given name and position in the
+/// scene is saved to.
+fn file_path(character: Option<&str>, index: usize) -> Option<PathBuf> {
+    config::config_dir().map(|dir| dir.join(file_name(character, index)))
+}
+
+/// Returns the name of the file the calibration of the character with the given name and position
+/// in the scene is saved to. Every character gets its own file, since each usually listens to a
+/// different mic. Named characters are told apart by name, so that reordering them keeps their
+/// calibrations, and unnamed ones by position.
+fn file_name(character: Option<&str>, index: usize) -> String {
+    match character {
+        Some(name) => {
+            // keep the name from reaching outside the config directory or upsetting the file system
+            let name: String = name
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || c == '-' || c == '_' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            format!("calibration-{name}.toml")
+        }
+        None if index == 0 => CALIBRATION_FILE_NAME.to_string(),
+        // sanitized names never contain dots, so these can't clash with named characters
+        None => format!("calibration.{}.toml", index + 1),
+    }
+}
+
+/// Listens to the input level for a while to measure the noise floor. The room should be as quiet
+/// as it usually is while streaming, but nobody should be talking.
+pub struct Calibrator {
+    /// When the calibration started.
+    start: Instant,
+
+    /// How long to listen for.
+    duration: Duration,
+
+    /// Levels heard so far, in dBFS.
+    levels: Vec<f32>,
+}
+
+impl Calibrator {
+    pub fn new(config: &CalibrationConfig) -> Self {
+        Self {
+            start: Instant::now(),
+            duration: Duration::from_secs_f32(config.duration_secs.max(0.0)),
+            levels: Vec::new(),
+        }
+    }
+
+    /// Records the current input level. Returns the finished calibration once enough time has
+    /// passed.
+    pub fn update(&mut self, level_dbfs: f32) -> Option<Calibration> {
+        self.levels.push(level_dbfs.max(MIN_NOISE_FLOOR_DBFS));
+
+        if self.start.elapsed() < self.duration {
+            return None;
+        }
+
+        self.levels.sort_by(f32::total_cmp);
+        let index = ((self.levels.len() - 1) as f32 * NOISE_FLOOR_PERCENTILE).round() as usize;
+
+        Some(Calibration {
+            noise_floor_dbfs: self.levels[index],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_character_gets_its_own_file() {
+        assert_eq!(file_name(None, 0), "calibration.toml");
+        assert_eq!(file_name(None, 1), "calibration.2.toml");
+        assert_eq!(file_name(Some("muni"), 0), "calibration-muni.toml");
+        assert_eq!(file_name(Some("muni"), 1), "calibration-muni.toml");
+    }
+
+    #[test]
+    fn names_are_sanitized() {
+        assert_eq!(
+            file_name(Some("../../.bashrc"), 0),
+            "calibration-_______bashrc.toml"
+        );
+        assert_eq!(file_name(Some("a/b c:d"), 0), "calibration-a_b_c_d.toml");
+    }
+}
@@ -0,0 +1,412 @@
+use std::time::Instant;
+
+use eframe::{
+    egui::{Context, Image, Rect, Sense, Stroke, Ui, Vec2},
+    epaint::Color32,
+};
+use serde::Deserialize;
+
+use crate::{
+    ExpressionChange, ExpressionState, POP_AMOUNT, POP_DURATION,
+    audio::{self, AnalysisFrame, AudioConfig},
+    calibration::{Calibration, CalibrationConfig, Calibrator},
+    crossfade::CrossfadeConfig,
+    eyes::{BlinkConfig, Eyes, GazeConfig},
+    head::Head,
+    head::{HeadMotionConfig, PhaseTiming},
+    idle::{self, IdleConfig, IdleScheduler},
+    keys::{self, HotkeyConfig},
+    meters::ChannelMeters,
+    paint_badge,
+    reaction::{YellConfig, YellReaction},
+    rules::{PitchRule, PitchRules},
+};
+
+/// Settings for one character in the scene.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CharacterConfig {
+    /// A name for the character, used to keep its calibration apart from the other characters'.
+    pub name: Option<String>,
+
+    /// Settings for the audio input driving the character.
+    pub audio: AudioConfig,
+
+    /// The character's hotkeys. Characters without hotkeys use the built-in ones on F1 to F12.
+    pub hotkeys: Option<HotkeyConfig>,
+
+    /// Rules that change the character's expression based on the pitch of the voice. The first
+    /// matching rule wins.
+    pub pitch_rules: Vec<PitchRule>,
+
+    /// How quickly the character's mouth changes shape.
+    pub mouth: PhaseTiming,
+
+    /// How the character's head and eyes fade between images.
+    pub crossfade: CrossfadeConfig,
+
+    /// How the character reacts to yelling.
+    pub yell: YellConfig,
+
+    /// How the character's head moves while talking.
+    pub head_motion: HeadMotionConfig,
+
+    /// How often the character blinks.
+    pub blink: BlinkConfig,
+
+    /// Where the character looks.
+    pub gaze: GazeConfig,
+
+    /// What the character does on its own while you're quiet.
+    pub idle: IdleConfig,
+
+    /// The expression to use while the character is muted.
+    pub muted_expression: Option<ExpressionChange>,
+
+    /// Whether to show a muted mic over the character while it's muted.
+    pub muted_icon: bool,
+}
+
+impl Default for CharacterConfig {
+    fn default() -> Self {
+        Self {
+            name: None,
+            audio: Default::default(),
+            hotkeys: None,
+            pitch_rules: Vec::new(),
+            mouth: Default::default(),
+            crossfade: Default::default(),
+            yell: Default::default(),
+            head_motion: Default::default(),
+            blink: Default::default(),
+            gaze: Default::default(),
+            idle: Default::default(),
+            muted_expression: None,
+            muted_icon: true,
+        }
+    }
+}
+
+/// A character in the scene, driven by its own audio input and hotkeys.
+pub struct Character<'a> {
+    /// The name of the character, if it has one.
+    name: Option<String>,
+
+    /// The position of the character in the scene, counting from 0 on the left.
+    index: usize,
+
+    /// The time at which the character appeared.
+    start: Instant,
+
+    /// The state of the audio input volume.
+    audio_state: audio::AudioState,
+
+    /// The image of the body to use.
+    body: Image<'a>,
+
+    /// The head of the character
+    head: Head<'a>,
+
+    /// The eyes state of the character.
+    eyes: Eyes<'a>,
+
+    /// The expression of the character.
+    expression: ExpressionState,
+
+    /// The hotkey manager for the character's expressions.
+    hotkey_manager: keys::ExpressionHotkeyManager,
+
+    /// Silences background noise below the calibrated noise floor.
+    noise_gate: audio::NoiseGate,
+
+    /// Settings for noise floor calibration.
+    calibration_config: CalibrationConfig,
+
+    /// The calibration in progress, if any.
+    calibrator: Option<Calibrator>,
+
+    /// Rules that change the expression based on the pitch of the voice.
+    pitch_rules: PitchRules,
+
+    /// Level meters for each channel of the input.
+    meters: ChannelMeters,
+
+    /// Shakes, punches and tints the character when it starts yelling.
+    yell_reaction: YellReaction,
+
+    /// Makes the character yawn, glance around and fall asleep while quiet.
+    idle: IdleScheduler,
+
+    /// Whether the mute hotkey has muted the character.
+    muted: bool,
+
+    /// Whether the character is muted at the moment, either by the mute hotkey or by push-to-talk.
+    is_muted: bool,
+
+    /// The expression to use while the character is muted.
+    muted_expression: Option<ExpressionChange>,
+
+    /// Whether to show a muted mic over the character while it's muted.
+    muted_icon: bool,
+}
+
+impl Character<'_> {
+    /// Creates the character at the given position in the scene, listening to the given audio
+    /// input.
+    pub fn new(
+        config: &CharacterConfig,
+        index: usize,
+        audio_state: audio::AudioState,
+        calibration_config: &CalibrationConfig,
+    ) -> Self {
+        let mut character = Self {
+            name: config.name.clone(),
+            index,
+            start: Instant::now(),
+            audio_state,
+
+            body: Image::from_bytes("bytes://body", include_bytes!("assets/body.png")),
+
+            head: Default::default(),
+            eyes: Default::default(),
+            expression: Default::default(),
+            hotkey_manager: config
+                .hotkeys
+                .as_ref()
+                .map(keys::ExpressionHotkeyManager::from_config)
+                .unwrap_or_default(),
+
+            noise_gate: Default::default(),
+            calibration_config: calibration_config.clone(),
+            calibrator: None,
+            pitch_rules: PitchRules::new(config.pitch_rules.clone()),
+
+            meters: Default::default(),
+            yell_reaction: YellReaction::new(&config.yell),
+            idle: IdleScheduler::new(&config.idle),
+
+            muted: false,
+            is_muted: false,
+            muted_expression: config.muted_expression.clone(),
+            muted_icon: config.muted_icon,
+        };
+
+        character
+            .head
+            .set_intensity_thresholds(config.audio.normalizer.thresholds);
+        character.head.set_phase_timing(&config.mouth);
+        character.head.set_crossfade(&config.crossfade);
+        character.head.set_motion(&config.head_motion);
+        character.eyes.set_blink(&config.blink);
+        character.eyes.set_gaze(&config.gaze);
+        character.eyes.set_crossfade(&config.crossfade);
+        if let Some(calibration) = Calibration::load(character.name.as_deref(), index) {
+            character.apply_calibration(&calibration);
+        }
+
+        character
+    }
+
+    /// Starts measuring the noise floor, unless there is no mic to measure.
+    pub fn start_calibration(&mut self) {
+        if self.audio_state.is_connected() {
+            eprintln!("calibrating noise floor{}, stay quiet...", self.describe());
+            self.calibrator = Some(Calibrator::new(&self.calibration_config));
+        } else {
+            eprintln!("can't calibrate{} without a mic", self.describe());
+        }
+    }
+
+    /// Feeds the current level to the calibration in progress, applying and saving the result
+    /// once it's done.
+    fn update_calibration(&mut self, volume: f32) {
+        let Some(calibration) = self.calibrator.as_mut().and_then(|c| c.update(volume)) else {
+            return;
+        };
+        self.calibrator = None;
+
+        eprintln!(
+            "measured noise floor{} at {:.1} dBFS",
+            self.describe(),
+            calibration.noise_floor_dbfs
+        );
+        self.apply_calibration(&calibration);
+        match calibration.save(self.name.as_deref(), self.index) {
+            Ok(path) => eprintln!("saved calibration to {}", path.display()),
+            Err(e) => eprintln!("couldn't save calibration: {e}"),
+        }
+    }
+
+    /// Sets the noise gate and speaking thresholds relative to the calibrated noise floor.
+    fn apply_calibration(&mut self, calibration: &Calibration) {
+        self.noise_gate.set_threshold(Some(
+            calibration.gate_threshold_dbfs(&self.calibration_config),
+        ));
+        self.head
+            .set_thresholds(calibration.speak_thresholds(&self.calibration_config));
+    }
+
+    /// Returns " for <name>" if the character has a name, for messages about it.
+    fn describe(&self) -> String {
+        self.name
+            .as_ref()
+            .map(|name| format!(" for {name}"))
+            .unwrap_or_default()
+    }
+
+    pub fn paint(&mut self, ctx: &Context, ui: &mut Ui) {
+        let pop_value = {
+            // quadratic function
+            let x = self.head.get_last_speak_start().elapsed().as_secs_f32();
+            let a = -4.0 / POP_DURATION.powi(2);
+            let b = -a * POP_DURATION;
+
+            a * x.powi(2) + b * x
+        }
+        .max(0.0);
+
+        let breath_value =
+            (self.start.elapsed().as_secs_f32() * 1.5).sin() + pop_value * POP_AMOUNT;
+        let breath_scale_x = 1.0 - breath_value / 200.0;
+        let breath_scale_y = 1.0 + breath_value / 200.0;
+
+        // yelling shakes, punches and tints the whole character on top of breathing
+        let yell = self
+            .yell_reaction
+            .effects(self.head.get_last_yell_start(), Instant::now());
+
+        // draw body
+        // let image_to_ui_height_ratio = ui.max_rect().height() / body_size.y;
+        let Some(body_size) = self.body.load_and_calc_size(ui, ui.max_rect().size()) else {
+            return;
+        };
+
+        let (rect, _) = ui.allocate_exact_size(
+            body_size * Vec2::new(breath_scale_x, breath_scale_y) * yell.scale,
+            Sense::hover(),
+        );
+        let rect = rect.translate(yell.offset);
+        self.body.clone().tint(yell.tint).paint_at(ui, rect);
+
+        // keep background noise out of the level, measuring it first if asked to
+        let frames = self.audio_state.poll();
+        self.meters.update(&frames, Instant::now());
+        for frame in frames {
+            self.update_calibration(frame.level_dbfs);
+            self.noise_gate.process(frame.level_dbfs);
+        }
+        let mut frame = self.audio_state.latest();
+        self.noise_gate.apply(&mut frame);
+
+        // a muted character hears nothing at all, so its mouth stays shut
+        if self.hotkey_manager.should_toggle_mute(ctx) {
+            self.muted = !self.muted;
+        }
+        self.is_muted = self.muted || self.hotkey_manager.is_push_to_talk_released(ctx);
+        if self.is_muted {
+            frame = AnalysisFrame {
+                time: frame.time,
+                channel_levels: frame.channel_levels,
+                ..AnalysisFrame::SILENT
+            };
+        }
+
+        // get some variables
+        let should_force_blink = self.hotkey_manager.should_force_blink(ctx);
+        if let Some(new_expression) = self.hotkey_manager.get_expression(ctx) {
+            self.expression.apply(new_expression)
+        }
+        let pitch_expression = self.pitch_rules.update(frame.pitch_hz, Instant::now());
+
+        // held hotkeys win over being muted, which wins over yawning and sleeping, which win
+        // over pitch rules, which win over the current expression
+        let now = Instant::now();
+        let idle = self.idle.update(self.head.get_last_open(), now);
+        let muted_expression = self.muted_expression.as_ref().filter(|_| self.is_muted);
+        let temporary_expressions = [
+            self.hotkey_manager.get_temporary_expression(ctx),
+            muted_expression,
+            idle.expression,
+            pitch_expression,
+        ];
+        let head_to_use = temporary_expressions
+            .iter()
+            .flatten()
+            .find_map(|e| e.head.as_ref())
+            .unwrap_or(&self.expression.head);
+        let eyes_to_use = temporary_expressions
+            .iter()
+            .flatten()
+            .find_map(|e| e.eyes.as_ref())
+            .unwrap_or(&self.expression.eyes);
+
+        // draw head and eyes, with the eyes following the head as it moves
+        let (head_rect, head_style) = self.head.paint(ui, rect, &frame, head_to_use, yell.tint);
+        let cursor = ctx
+            .pointer_hover_pos()
+            .map(|pos| (pos - head_rect.center()) / (head_rect.size() / 2.0));
+        let look = self.hotkey_manager.get_look_direction(ctx).or(idle.look);
+        self.eyes.look(look, cursor);
+        self.eyes.paint(
+            ui,
+            head_rect,
+            eyes_to_use,
+            should_force_blink || idle.asleep_since.is_some(),
+            self.head.is_mouth_open(),
+            head_style,
+        );
+
+        if let Some(asleep_since) = idle.asleep_since.filter(|_| idle.zzz) {
+            idle::paint_zzz(ui, head_rect, now.duration_since(asleep_since));
+        }
+    }
+
+    /// Paints status badges and, if asked to, the channel meters over the character.
+    pub fn paint_status(&self, ui: &Ui, show_meters: bool) {
+        if !self.audio_state.is_connected() {
+            paint_badge(ui, "no mic");
+        } else if self.calibrator.is_some() {
+            paint_badge(ui, "calibrating, stay quiet...");
+        }
+
+        if self.is_muted && self.muted_icon {
+            paint_muted_icon(ui);
+        }
+
+        if show_meters {
+            self.meters.paint(ui);
+        }
+    }
+}
+
+/// Paints a crossed-out mic in the top right corner of the given ui.
+fn paint_muted_icon(ui: &Ui) {
+    let painter = ui.painter();
+    let center = ui.max_rect().right_top() + Vec2::new(-32.0, 32.0);
+    let stroke = Stroke::new(3.0, Color32::WHITE);
+
+    painter.circle_filled(center, 22.0, Color32::from_rgb(200, 40, 40));
+
+    // the mic's capsule, its stand and its base
+    let capsule = Rect::from_center_size(center - Vec2::new(0.0, 4.0), Vec2::new(10.0, 18.0));
+    painter.rect_filled(capsule, 5.0, Color32::WHITE);
+    painter.line_segment(
+        [center + Vec2::new(0.0, 8.0), center + Vec2::new(0.0, 13.0)],
+        stroke,
+    );
+    painter.line_segment(
+        [
+            center + Vec2::new(-6.0, 13.0),
+            center + Vec2::new(6.0, 13.0),
+        ],
+        stroke,
+    );
+
+    // the slash across it, outlined so that it stands out from the mic
+    let slash = [
+        center + Vec2::new(-12.0, -12.0),
+        center + Vec2::new(12.0, 12.0),
+    ];
+    painter.line_segment(slash, Stroke::new(7.0, Color32::from_rgb(200, 40, 40)));
+    painter.line_segment(slash, stroke);
+}
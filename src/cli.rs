@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use crate::audio::DeviceSelector;
+
+pub const USAGE: &str = "\
+usage: muni-tuber [options]
+
+options:
+    --config <path>          read the config from <path> instead of the default location
+    --device <name|index>    use the given input device instead of the configured one
+    --file <path>            read input from a WAV file instead of a device
+    --analyze <path>         print the analysis of a WAV file as CSV and exit
+    --calibrate              measure the noise floor right after starting
+    --check                  check the config for unknown or unused expressions and exit,
+                             failing if any errors are found
+    --list-devices           print the available input devices and exit
+    -h, --help               print this help and exit";
+
+/// Command line arguments.
+#[derive(Debug, Default)]
+pub struct Args {
+    /// A config file to use instead of the default one.
+    pub config: Option<PathBuf>,
+
+    /// An input device overriding the one in the config.
+    pub device: Option<DeviceSelector>,
+
+    /// A WAV file to read input from instead of a device.
+    pub file: Option<PathBuf>,
+
+    /// A WAV file to analyze and print the result of, instead of running the app.
+    pub analyze: Option<PathBuf>,
+
+    /// Whether to calibrate the noise floor on startup.
+    pub calibrate: bool,
+
+    /// Whether to check the config for problems and exit.
+    pub check: bool,
+
+    /// Whether to print the available input devices and exit.
+    pub list_devices: bool,
+
+    /// Whether to print usage and exit.
+    pub help: bool,
+}
+
+impl Args {
+    /// Parses the arguments the app was started with.
+    pub fn parse() -> Result<Self, String> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => parsed.config = Some(Self::value_of(&arg, args.next())?.into()),
+                "--device" => {
+                    parsed.device = Some(DeviceSelector::parse(&Self::value_of(&arg, args.next())?))
+                }
+                "--file" => parsed.file = Some(Self::value_of(&arg, args.next())?.into()),
+                "--analyze" => parsed.analyze = Some(Self::value_of(&arg, args.next())?.into()),
+                "--calibrate" => parsed.calibrate = true,
+                "--check" => parsed.check = true,
+                "--list-devices" => parsed.list_devices = true,
+                "-h" | "--help" => parsed.help = true,
+                _ => return Err(format!("unknown argument '{arg}'")),
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn value_of(arg: &str, value: Option<String>) -> Result<String, String> {
+        value.ok_or_else(|| format!("'{arg}' needs a value"))
+    }
+}
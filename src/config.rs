@@ -0,0 +1,100 @@
+use std::{
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    audio::AudioConfig, calibration::CalibrationConfig, character::CharacterConfig,
+    rules::PitchRule,
+};
+
+/// The name of the config file inside the app's config directory.
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// User configuration for the app. Every field is optional in the config file; anything left out
+/// falls back to its default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Settings for audio input. Only used if no characters are configured.
+    pub audio: AudioConfig,
+
+    /// Settings for noise floor calibration.
+    pub calibration: CalibrationConfig,
+
+    /// Rules that change the expression based on the pitch of the voice. The first matching rule
+    /// wins. Only used if no characters are configured.
+    pub pitch_rules: Vec<PitchRule>,
+
+    /// The characters in the scene, from left to right.
+    pub characters: Vec<CharacterConfig>,
+}
+
+impl Config {
+    /// Loads the config from the given path, or from the default location if `path` is `None`. A
+    /// missing file at the default location is not an error; the default config is used instead.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                toml::from_str(&contents).map_err(|e| ConfigError::Parse(path, Box::new(e)))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Ok(Self::default()),
+            Err(e) => Err(ConfigError::Io(path, e)),
+        }
+    }
+
+    /// Returns the characters in the scene. If none are configured, the scene has a single
+    /// character using the top-level audio settings and pitch rules.
+    pub fn characters(&self) -> Vec<CharacterConfig> {
+        if !self.characters.is_empty() {
+            return self.characters.clone();
+        }
+
+        vec![CharacterConfig {
+            audio: self.audio.clone(),
+            pitch_rules: self.pitch_rules.clone(),
+            ..Default::default()
+        }]
+    }
+}
+
+/// Returns the directory in which the app stores its configuration, if one can be determined.
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("muni-tuber"))
+}
+
+/// Returns the default path of the config file.
+fn default_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(CONFIG_FILE_NAME))
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The config file could not be read.
+    Io(PathBuf, io::Error),
+
+    /// The config file could not be parsed.
+    Parse(PathBuf, Box<toml::de::Error>),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "couldn't read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "couldn't parse {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
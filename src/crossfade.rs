@@ -0,0 +1,202 @@
+use std::time::{Duration, Instant};
+
+use eframe::{
+    egui::{Image, Ui, Vec2},
+    epaint::{Color32, Rect},
+};
+use serde::Deserialize;
+
+/// Settings for fading between images when the character changes expression or mouth shape.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CrossfadeConfig {
+    /// How long a fade takes, in milliseconds. Images are swapped instantly if this is 0.
+    pub duration_ms: f32,
+
+    /// How the fade speeds up and slows down.
+    pub easing: Easing,
+}
+
+impl Default for CrossfadeConfig {
+    fn default() -> Self {
+        Self {
+            duration_ms: 0.0,
+            easing: Default::default(),
+        }
+    }
+}
+
+/// How a fade progresses over its duration.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    /// At the same speed throughout.
+    Linear,
+
+    /// Slowly at first, then quickly.
+    EaseIn,
+
+    /// Quickly at first, then slowly.
+    EaseOut,
+
+    /// Slowly at the start and end, quickly in the middle.
+    #[default]
+    EaseInOut,
+}
+
+impl Easing {
+    /// Returns how far along the fade is, from 0 to 1, at the given fraction of its duration.
+    fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Self::Linear => t,
+            Self::EaseIn => t * t,
+            Self::EaseOut => t * (2.0 - t),
+            Self::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// How to tint and rotate the images of a layer of the character.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImageStyle {
+    /// The color to tint the images with.
+    pub tint: Color32,
+
+    /// How far to rotate the images clockwise, in radians.
+    pub angle: f32,
+
+    /// The point to rotate the images around, from 0 to 1 across and down the image.
+    pub pivot: Vec2,
+}
+
+impl ImageStyle {
+    /// Returns the image tinted and rotated in this style, with its opacity multiplied by
+    /// `opacity`.
+    pub fn apply<'a>(&self, image: &Image<'a>, opacity: f32) -> Image<'a> {
+        image
+            .clone()
+            .tint(self.tint.gamma_multiply(opacity))
+            .rotate(self.angle, self.pivot)
+    }
+}
+
+/// Returns the opacities of the outgoing and incoming images the given fraction of the way through a
+/// fade. The incoming image fades in over the outgoing one, which stays opaque, so that whatever is
+/// behind the layer never shows through where both images are opaque.
+fn opacities(progress: f32) -> (f32, f32) {
+    (1.0, progress.clamp(0.0, 1.0))
+}
+
+/// Paints an image, fading from the previously painted one whenever the caller says the image
+/// changed.
+pub struct Crossfade<'a> {
+    /// How long a fade takes.
+    duration: Duration,
+
+    /// How the fade progresses.
+    easing: Easing,
+
+    /// The image being faded out, and the time at which the fade started.
+    fading_out: Option<(Image<'a>, Instant)>,
+
+    /// The image painted last.
+    current: Option<Image<'a>>,
+}
+
+impl<'a> Crossfade<'a> {
+    pub fn new(config: &CrossfadeConfig) -> Self {
+        Self {
+            duration: Duration::from_secs_f32(config.duration_ms.max(0.0) / 1000.0),
+            easing: config.easing,
+            fading_out: None,
+            current: None,
+        }
+    }
+
+    /// Paints the given image over the given rectangle in the given style. If `changed` is set,
+    /// this one fades in over the image painted last.
+    pub fn paint(
+        &mut self,
+        ui: &mut Ui,
+        rect: Rect,
+        image: &Image<'a>,
+        style: ImageStyle,
+        changed: bool,
+        now: Instant,
+    ) {
+        if changed
+            && !self.duration.is_zero()
+            && let Some(previous) = self.current.take()
+        {
+            self.fading_out = Some((previous, now));
+        }
+        self.current = Some(image.clone());
+
+        let progress = match &self.fading_out {
+            Some((_, started)) => self
+                .easing
+                .apply(now.duration_since(*started).as_secs_f32() / self.duration.as_secs_f32()),
+            None => 1.0,
+        };
+        if progress >= 1.0 {
+            self.fading_out = None;
+            style.apply(image, 1.0).paint_at(ui, rect);
+            return;
+        }
+
+        let (outgoing, incoming) = opacities(progress);
+        if let Some((previous, _)) = &self.fading_out {
+            style.apply(previous, outgoing).paint_at(ui, rect);
+        }
+        style.apply(image, incoming).paint_at(ui, rect);
+    }
+}
+
+impl Default for Crossfade<'_> {
+    fn default() -> Self {
+        Self::new(&Default::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 4] = [
+        Easing::Linear,
+        Easing::EaseIn,
+        Easing::EaseOut,
+        Easing::EaseInOut,
+    ];
+
+    #[test]
+    fn easings_run_from_0_to_1() {
+        for easing in EASINGS {
+            assert_eq!(easing.apply(-1.0), 0.0);
+            assert_eq!(easing.apply(0.0), 0.0);
+            assert_eq!(easing.apply(1.0), 1.0);
+            assert_eq!(easing.apply(2.0), 1.0);
+
+            let mut last = 0.0;
+            for step in 1..=100 {
+                let progress = easing.apply(step as f32 / 100.0);
+                assert!(progress >= last, "{easing:?} goes backwards");
+                last = progress;
+            }
+        }
+    }
+
+    #[test]
+    fn fade_never_lets_the_background_through() {
+        for easing in EASINGS {
+            for step in 0..=100 {
+                let (outgoing, incoming) = opacities(easing.apply(step as f32 / 100.0));
+
+                // the coverage of the incoming image painted over the outgoing one
+                let coverage = incoming + outgoing * (1.0 - incoming);
+                assert!((coverage - 1.0).abs() < 1e-6, "{easing:?} at {step}%");
+            }
+        }
+    }
+}
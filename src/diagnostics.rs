@@ -0,0 +1,288 @@
+use std::{
+    collections::HashSet,
+    fmt::{self, Display},
+};
+
+use eframe::{
+    egui::{Align2, FontId, Rect, Ui, Vec2, pos2},
+    epaint::Color32,
+};
+
+use crate::{
+    ExpressionChange, character::CharacterConfig, eyes::Eyes, head::Head,
+    keys::ExpressionHotkeyManager,
+};
+
+/// How bad a problem found in the setup is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Something is definitely wrong, such as a typo in an expression name.
+    Error,
+
+    /// Something looks off but may be intended, such as an expression that is never used.
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in the setup of a character.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+
+    /// The name of the character with the problem, if it has one.
+    pub character: Option<String>,
+
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)?;
+        if let Some(name) = &self.character {
+            write!(f, " for {name}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the given characters for expression names that don't exist, expressions that are never
+/// used and expressions missing images. Errors come first.
+pub fn check(characters: &[CharacterConfig]) -> Vec<Diagnostic> {
+    let head = Head::default();
+    let eyes = Eyes::default();
+
+    let mut diagnostics: Vec<_> = characters
+        .iter()
+        .flat_map(|character| check_character(character, &head, &eyes))
+        .collect();
+    diagnostics.sort_by(|a, b| (a.severity, &a.message).cmp(&(b.severity, &b.message)));
+    diagnostics.dedup();
+    diagnostics
+}
+
+/// Returns whether any of the given diagnostics is an error.
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(|d| d.severity == Severity::Error)
+}
+
+fn check_character(config: &CharacterConfig, head: &Head, eyes: &Eyes) -> Vec<Diagnostic> {
+    let diagnostic = |severity, message| Diagnostic {
+        severity,
+        character: config.name.clone(),
+        message,
+    };
+
+    // every place the character's expressions are named, with a description of where it is
+    let hotkeys = config
+        .hotkeys
+        .as_ref()
+        .map(ExpressionHotkeyManager::from_config)
+        .unwrap_or_default();
+    let mut changes: Vec<(String, &ExpressionChange)> = Vec::new();
+    for (key, change) in &hotkeys.expression_switches {
+        changes.push((format!("hotkey {}", key.name()), change));
+    }
+    for (key, change) in &hotkeys.expression_holds {
+        changes.push((format!("held hotkey {}", key.name()), change));
+    }
+    for (index, rule) in config.pitch_rules.iter().enumerate() {
+        changes.push((format!("pitch rule {}", index + 1), &rule.expression));
+    }
+    if let Some(change) = &config.muted_expression {
+        changes.push(("muted_expression".to_string(), change));
+    }
+    if let Some(yawn) = &config.idle.yawn {
+        changes.push(("the idle yawn".to_string(), &yawn.expression));
+    }
+    if let Some(change) = &config.idle.sleep_expression {
+        changes.push(("the idle sleep_expression".to_string(), change));
+    }
+
+    let mut diagnostics = Vec::new();
+    let mut used_heads = HashSet::new();
+    let mut used_eyes = HashSet::new();
+
+    for (place, change) in &changes {
+        if let Some(name) = &change.head {
+            used_heads.insert(name.as_str());
+            if !head.has_expression(name) {
+                diagnostics.push(diagnostic(
+                    Severity::Error,
+                    format!("{place} uses unknown head expression '{name}'"),
+                ));
+            }
+        }
+        if let Some(name) = &change.eyes {
+            used_eyes.insert(name.as_str());
+            if !eyes.has_expression(name) {
+                diagnostics.push(diagnostic(
+                    Severity::Error,
+                    format!("{place} uses unknown eyes expression '{name}'"),
+                ));
+            }
+        }
+    }
+
+    for name in config.blink.expressions.keys() {
+        if !eyes.has_expression(name) {
+            diagnostics.push(diagnostic(
+                Severity::Error,
+                format!("blink settings are given for unknown eyes expression '{name}'"),
+            ));
+        }
+    }
+
+    for name in head.expression_names() {
+        if !used_heads.contains(name) {
+            diagnostics.push(diagnostic(
+                Severity::Warning,
+                format!("head expression '{name}' is never used"),
+            ));
+        }
+        if !head.has_mouth_images(name) {
+            diagnostics.push(diagnostic(
+                Severity::Warning,
+                format!("head expression '{name}' has no images for an open mouth"),
+            ));
+        }
+    }
+    for name in eyes.expression_names() {
+        if !used_eyes.contains(name) {
+            diagnostics.push(diagnostic(
+                Severity::Warning,
+                format!("eyes expression '{name}' is never used"),
+            ));
+        }
+    }
+
+    diagnostics
+}
+
+/// Paints the given diagnostics in the bottom left corner of the given ui, one per line.
+pub fn paint_overlay(ui: &Ui, diagnostics: &[Diagnostic]) {
+    let painter = ui.painter();
+    let font = FontId::proportional(14.0);
+    let line_height = 18.0;
+    let corner = ui.max_rect().left_bottom() + Vec2::new(12.0, -12.0);
+
+    let lines = diagnostics.len() as f32 + 1.0;
+    let top = corner.y - lines * line_height;
+    let width = ui.max_rect().width() - 24.0;
+    let background =
+        Rect::from_min_size(pos2(corner.x, top), Vec2::new(width, lines * line_height));
+    painter.rect_filled(background.expand(6.0), 4.0, Color32::from_black_alpha(200));
+
+    painter.text(
+        pos2(corner.x, top),
+        Align2::LEFT_TOP,
+        "problems found at startup (Page Up to hide):",
+        font.clone(),
+        Color32::WHITE,
+    );
+    for (index, diagnostic) in diagnostics.iter().enumerate() {
+        let color = match diagnostic.severity {
+            Severity::Error => Color32::from_rgb(255, 110, 110),
+            Severity::Warning => Color32::from_rgb(255, 210, 90),
+        };
+        painter.text(
+            pos2(corner.x, top + (index as f32 + 1.0) * line_height),
+            Align2::LEFT_TOP,
+            diagnostic.to_string(),
+            font.clone(),
+            color,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_toml(toml: &str) -> Vec<Diagnostic> {
+        check(&[toml::from_str(toml).unwrap()])
+    }
+
+    fn errors(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn default_config_has_no_errors() {
+        let diagnostics = check(&[CharacterConfig::default()]);
+        assert!(!has_errors(&diagnostics), "{diagnostics:?}");
+    }
+
+    #[test]
+    fn unknown_expressions_are_errors() {
+        let diagnostics = check_toml(
+            r#"
+            [hotkeys]
+            switches = { 1 = { eyes = "angyr" } }
+            holds = { Space = { head = "frwon" } }
+            "#,
+        );
+        assert_eq!(
+            errors(&diagnostics),
+            [
+                "held hotkey Space uses unknown head expression 'frwon'",
+                "hotkey 1 uses unknown eyes expression 'angyr'",
+            ]
+        );
+        assert!(has_errors(&diagnostics));
+    }
+
+    #[test]
+    fn blink_settings_for_unknown_expressions_are_errors() {
+        let diagnostics = check_toml("[blink.expressions.sleepyy]\nmean_delay_secs = 6.0");
+        assert_eq!(
+            errors(&diagnostics),
+            ["blink settings are given for unknown eyes expression 'sleepyy'"]
+        );
+    }
+
+    #[test]
+    fn unused_expressions_are_warnings() {
+        let diagnostics = check_toml(
+            r#"
+            [hotkeys]
+            switches = { 1 = { eyes = "normal", head = "happy" } }
+            "#,
+        );
+        assert!(!has_errors(&diagnostics));
+        assert!(diagnostics.iter().any(|d| d.severity == Severity::Warning
+            && d.message == "head expression 'frown' is never used"));
+        assert!(
+            !diagnostics
+                .iter()
+                .any(|d| d.message == "head expression 'happy' is never used")
+        );
+    }
+
+    #[test]
+    fn only_errors_fail_the_check() {
+        let warning = Diagnostic {
+            severity: Severity::Warning,
+            character: None,
+            message: "head expression 'wavy' is never used".to_string(),
+        };
+        let error = Diagnostic {
+            severity: Severity::Error,
+            ..warning.clone()
+        };
+        assert!(!has_errors(&[]));
+        assert!(!has_errors(std::slice::from_ref(&warning)));
+        assert!(has_errors(&[warning, error]));
+    }
+}
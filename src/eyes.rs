@@ -1,175 +1,222 @@
-use eframe::{
-    egui::{Image, Ui},
-    epaint::Rect,
-};
+mod blink;
+mod gaze;
+
 use std::{
     collections::HashMap,
     time::{Duration, Instant},
 };
 
-/// The minimum delay between blinks, in seconds.
-const BLINK_MIN_DELAY: f32 = 1.0;
+use eframe::{
+    egui::{Image, Ui, Vec2},
+    epaint::Rect,
+};
 
-/// The maximum delay between blinks, in seconds.
-const BLINK_MAX_DELAY: f32 = 5.0;
+use crate::{
+    crossfade::{Crossfade, CrossfadeConfig, ImageStyle},
+    flipbook::Flipbook,
+};
+pub use blink::BlinkConfig;
+use blink::BlinkTimer;
+use gaze::Gaze;
+pub use gaze::GazeConfig;
 
-/// The duration of a blink, in seconds.
-const BLINK_SECONDS: f32 = 0.2;
+/// How long the built-in eyes stay closed when they blink.
+const BLINK_TIME: Duration = Duration::from_millis(200);
 
 pub struct Eyes<'a> {
-    /// Instant to keep track of when the character last changed phases.
-    last_blink: Instant,
+    /// Decides when the eyes blink.
+    blink_timer: BlinkTimer,
 
     /// The expression used in the last frame.
     last_expression_name: String,
 
-    /// The duration until the next blink.
-    next_blink_time: Duration,
-
-    /// The current phase of the blink animation.
-    blink_phase: BlinkPhase,
-
     /// Images to fallback to when an expression is not found.
     default_expression: EyesExpression<'a>,
 
     /// Expression images to use when the character's eyes are open.
     expressions: HashMap<String, EyesExpression<'a>>,
+
+    /// Fades between images when the expression changes.
+    crossfade: Crossfade<'a>,
+
+    /// Tracks where the character looks.
+    gaze: Gaze,
+
+    /// How far the pupils are moved from the center of the eyes.
+    pupil_offset: Vec2,
 }
 
 impl Default for Eyes<'_> {
     fn default() -> Self {
-        let now = Instant::now();
-        let next_blink_time = Self::random_blink_delay();
-
         Self {
-            last_blink: now,
+            blink_timer: BlinkTimer::new(&Default::default(), Instant::now()),
             last_expression_name: String::new(),
-            next_blink_time,
-            blink_phase: BlinkPhase::Open,
             default_expression: EyesExpression {
-                idle: Image::from_bytes(
+                idle: Flipbook::still(Image::from_bytes(
                     "bytes://eyes_default_open",
                     include_bytes!("assets/eyes_normal_open.png"),
-                ),
-                blink: Some(Image::from_bytes(
-                    "bytes://eyes_default_closed",
-                    include_bytes!("assets/eyes_normal_closed.png"),
                 )),
+                pupils: None,
+                blink: vec![(
+                    Flipbook::still(Image::from_bytes(
+                        "bytes://eyes_default_closed",
+                        include_bytes!("assets/eyes_normal_closed.png"),
+                    )),
+                    BLINK_TIME,
+                )],
             },
             expressions: HashMap::from([
+                (
+                    "normal".to_string(),
+                    EyesExpression {
+                        idle: Flipbook::still(Image::from_bytes(
+                            "bytes://eyes_normal_open",
+                            include_bytes!("assets/eyes_normal_open.png"),
+                        )),
+                        pupils: None,
+                        blink: vec![(
+                            Flipbook::still(Image::from_bytes(
+                                "bytes://eyes_normal_closed",
+                                include_bytes!("assets/eyes_normal_closed.png"),
+                            )),
+                            BLINK_TIME,
+                        )],
+                    },
+                ),
                 (
                     "sad".to_string(),
                     EyesExpression {
-                        idle: Image::from_bytes(
+                        idle: Flipbook::still(Image::from_bytes(
                             "bytes://eyes_sad_open",
                             include_bytes!("assets/eyes_sad_open.png"),
-                        ),
-                        blink: Some(Image::from_bytes(
-                            "bytes://eyes_sad_closed",
-                            include_bytes!("assets/eyes_sad_closed.png"),
                         )),
+                        pupils: None,
+                        blink: vec![(
+                            Flipbook::still(Image::from_bytes(
+                                "bytes://eyes_sad_closed",
+                                include_bytes!("assets/eyes_sad_closed.png"),
+                            )),
+                            BLINK_TIME,
+                        )],
                     },
                 ),
                 (
                     "angry".to_string(),
                     EyesExpression {
-                        idle: Image::from_bytes(
+                        idle: Flipbook::still(Image::from_bytes(
                             "bytes://eyes_angry_open",
                             include_bytes!("assets/eyes_angry_open.png"),
-                        ),
-                        blink: Some(Image::from_bytes(
-                            "bytes://eyes_angry_closed",
-                            include_bytes!("assets/eyes_angry_closed.png"),
                         )),
+                        pupils: None,
+                        blink: vec![(
+                            Flipbook::still(Image::from_bytes(
+                                "bytes://eyes_angry_closed",
+                                include_bytes!("assets/eyes_angry_closed.png"),
+                            )),
+                            BLINK_TIME,
+                        )],
                     },
                 ),
                 (
                     "wide".to_string(),
                     EyesExpression {
-                        idle: Image::from_bytes(
+                        idle: Flipbook::still(Image::from_bytes(
                             "bytes://eyes_wide_open",
                             include_bytes!("assets/eyes_wide.png"),
-                        ),
-                        blink: Some(Image::from_bytes(
-                            "bytes://eyes_tight_shut",
-                            include_bytes!("assets/eyes_tight.png"),
                         )),
+                        pupils: None,
+                        blink: vec![(
+                            Flipbook::still(Image::from_bytes(
+                                "bytes://eyes_tight_shut",
+                                include_bytes!("assets/eyes_tight.png"),
+                            )),
+                            BLINK_TIME,
+                        )],
                     },
                 ),
                 (
                     "dreamy".to_string(),
                     EyesExpression {
-                        idle: Image::from_bytes(
+                        idle: Flipbook::still(Image::from_bytes(
                             "bytes://eyes_dreamy_open",
                             include_bytes!("assets/eyes_dreamy_open.png"),
-                        ),
-                        blink: Some(Image::from_bytes(
-                            "bytes://eyes_dreamy_closed",
-                            include_bytes!("assets/eyes_dreamy_closed.png"),
                         )),
+                        pupils: None,
+                        blink: vec![(
+                            Flipbook::still(Image::from_bytes(
+                                "bytes://eyes_dreamy_closed",
+                                include_bytes!("assets/eyes_dreamy_closed.png"),
+                            )),
+                            BLINK_TIME,
+                        )],
                     },
                 ),
                 (
                     "happy".to_string(),
                     EyesExpression {
-                        idle: Image::from_bytes(
+                        idle: Flipbook::still(Image::from_bytes(
                             "bytes://eyes_smiling",
                             include_bytes!("assets/eyes_happy.png"),
-                        ),
-                        blink: None,
+                        )),
+                        pupils: None,
+                        blink: Vec::new(),
                     },
                 ),
                 (
                     "tight".to_string(),
                     EyesExpression {
-                        idle: Image::from_bytes(
+                        idle: Flipbook::still(Image::from_bytes(
                             "bytes://eyes_tight",
                             include_bytes!("assets/eyes_tight.png"),
-                        ),
-                        blink: None,
+                        )),
+                        pupils: None,
+                        blink: Vec::new(),
                     },
                 ),
             ]),
+            crossfade: Default::default(),
+            gaze: Gaze::new(&Default::default(), Instant::now()),
+            pupil_offset: Vec2::ZERO,
         }
     }
 }
 
-impl Eyes<'_> {
-    /// Returns a random duration between `BLINK_MIN_DELAY` and `BLINK_MAX_DELAY`.
-    fn random_blink_delay() -> Duration {
-        let delay = rand::random::<f32>() * (BLINK_MAX_DELAY - BLINK_MIN_DELAY) + BLINK_MIN_DELAY;
-        Duration::from_secs_f32(delay)
+impl<'a> Eyes<'a> {
+    /// Returns the names of the eyes' expressions.
+    pub fn expression_names(&self) -> impl Iterator<Item = &str> {
+        self.expressions.keys().map(String::as_str)
     }
 
-    /// Updates the state of the blinking animation.
-    pub fn update(&mut self) {
-        // get the time now
-        let now = Instant::now();
-
-        // if the time now has passed the next blink time, blink. set last_blink to now and
-        // next_blink to some random delay.
-        if now >= self.last_blink + self.next_blink_time {
-            self.last_blink = now;
-            self.next_blink_time = Self::random_blink_delay();
-        }
+    pub fn has_expression(&self, name: &str) -> bool {
+        self.expressions.contains_key(name)
+    }
 
-        // determine if the eyes are closed now or not
-        self.blink_phase = if now <= self.last_blink + Duration::from_secs_f32(BLINK_SECONDS) {
-            BlinkPhase::Closed
-        } else {
-            BlinkPhase::Open
-        }
+    /// Moves the pupils towards where the character should look. `look` is a direction to look in
+    /// from a hotkey, and `cursor` the direction of the mouse cursor, both from -1 to 1 across and
+    /// down.
+    pub fn look(&mut self, look: Option<Vec2>, cursor: Option<Vec2>) {
+        self.pupil_offset = self.gaze.update(look, cursor, Instant::now());
     }
 
     /// Paints the eyes over the given rectangle. The rectangle should be the rectangle over which
-    /// the head base was painted.
-    pub fn paint(&mut self, ui: &mut Ui, rect: Rect, expression_name: &str, force_shut: bool) {
+    /// the head base was painted, and the eyes are painted in the same style as the head. The
+    /// eyes may blink when the character stops speaking.
+    pub fn paint(
+        &mut self,
+        ui: &mut Ui,
+        rect: Rect,
+        expression_name: &str,
+        force_shut: bool,
+        speaking: bool,
+        style: ImageStyle,
+    ) {
+        let now = Instant::now();
+
         // blink now if our expression has changed
-        if expression_name != self.last_expression_name {
-            self.last_blink = Instant::now();
+        let changed = expression_name != self.last_expression_name;
+        if changed {
+            self.blink_timer.blink_now(now);
             self.last_expression_name = expression_name.to_string();
-        } else {
-            self.update();
         }
 
         // get the expression to use, or fallback to default
@@ -177,25 +224,131 @@ impl Eyes<'_> {
             .expressions
             .get(expression_name)
             .unwrap_or(&self.default_expression);
+        self.blink_timer
+            .update(expression_name, expression.blink_duration(), speaking, now);
 
-        // decide which image to use
-        let img = if matches!(self.blink_phase, BlinkPhase::Closed) || force_shut {
-            expression.blink.as_ref().unwrap_or(&expression.idle)
+        // decide which image to use, animating it from the start of the last blink
+        let since_blink = now.duration_since(self.blink_timer.last_blink());
+        let blink_frame = if force_shut {
+            expression.closed()
         } else {
-            &expression.idle
+            expression.blink_frame(since_blink)
         };
+        let img = blink_frame
+            .unwrap_or(&expression.idle)
+            .frame_at(since_blink);
 
         // paint the image over the given rectangle
-        img.paint_at(ui, rect)
+        self.crossfade.paint(ui, rect, img, style, changed, now);
+
+        // the pupils only show while the eyes are open
+        if let (None, Some(pupils)) = (blink_frame, &expression.pupils) {
+            style
+                .apply(pupils.frame_at(since_blink), 1.0)
+                .paint_at(ui, rect.translate(self.pupil_offset));
+        }
+    }
+
+    pub fn set_blink(&mut self, config: &BlinkConfig) {
+        self.blink_timer = BlinkTimer::new(config, Instant::now());
+    }
+
+    pub fn set_gaze(&mut self, config: &GazeConfig) {
+        self.gaze = Gaze::new(config, Instant::now());
     }
-}
 
-enum BlinkPhase {
-    Open,
-    Closed,
+    pub fn set_crossfade(&mut self, config: &CrossfadeConfig) {
+        self.crossfade = Crossfade::new(config);
+    }
 }
 
 pub struct EyesExpression<'a> {
-    pub idle: Image<'a>,
-    pub blink: Option<Image<'a>>,
+    pub idle: Flipbook<'a>,
+
+    /// The pupils, moved around within the eyes to follow the character's gaze. If these are
+    /// set, `idle` should be drawn without pupils.
+    pub pupils: Option<Flipbook<'a>>,
+
+    /// The frames of a blink in order, each with how long it's shown, such as half shut, closed
+    /// and half shut again. The eyes are open before the first frame and after the last, and the
+    /// frame in the middle is the one with the eyes fully closed. Eyes without any frames don't
+    /// blink.
+    pub blink: Vec<(Flipbook<'a>, Duration)>,
+}
+
+impl<'a> EyesExpression<'a> {
+    /// Returns the time from the start of a blink until the eyes are fully open again.
+    fn blink_duration(&self) -> Duration {
+        self.blink.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// Returns the blink frame to show the given time after a blink started, or `None` if the
+    /// blink is over.
+    fn blink_frame(&self, since_blink: Duration) -> Option<&Flipbook<'a>> {
+        let mut remaining = since_blink;
+        for (frame, duration) in &self.blink {
+            if remaining < *duration {
+                return Some(frame);
+            }
+            remaining -= *duration;
+        }
+        None
+    }
+
+    /// Returns the frame with the eyes fully closed, if the eyes can close.
+    fn closed(&self) -> Option<&Flipbook<'a>> {
+        self.blink.get(self.blink.len() / 2).map(|(frame, _)| frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn still(uri: &'static str) -> Flipbook<'static> {
+        Flipbook::still(Image::from_uri(uri))
+    }
+
+    fn uri<'a>(frame: Option<&'a Flipbook>) -> Option<&'a str> {
+        frame.map(|frame| frame.frame_at(Duration::ZERO).uri().unwrap())
+    }
+
+    #[test]
+    fn blink_frames_play_in_order() {
+        let expression = EyesExpression {
+            idle: still("open"),
+            pupils: None,
+            blink: vec![
+                (still("half"), ms(40)),
+                (still("closed"), ms(100)),
+                (still("half"), ms(60)),
+            ],
+        };
+        assert_eq!(expression.blink_duration(), ms(200));
+        let at = |t| uri(expression.blink_frame(ms(t)));
+        assert_eq!(at(0), Some("half"));
+        assert_eq!(at(39), Some("half"));
+        assert_eq!(at(40), Some("closed"));
+        assert_eq!(at(139), Some("closed"));
+        assert_eq!(at(140), Some("half"));
+        assert_eq!(at(199), Some("half"));
+        assert_eq!(at(200), None);
+        assert_eq!(uri(expression.closed()), Some("closed"));
+    }
+
+    #[test]
+    fn eyes_without_blink_frames_stay_open() {
+        let expression = EyesExpression {
+            idle: still("open"),
+            pupils: None,
+            blink: Vec::new(),
+        };
+        assert_eq!(expression.blink_duration(), Duration::ZERO);
+        assert_eq!(uri(expression.blink_frame(Duration::ZERO)), None);
+        assert_eq!(uri(expression.closed()), None);
+    }
 }
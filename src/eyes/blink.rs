@@ -0,0 +1,331 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+/// Settings for how often a character blinks.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlinkConfig {
+    /// How delays between blinks are picked.
+    pub distribution: BlinkDistribution,
+
+    /// How tightly delays cluster around the average with the gamma distribution. Higher shapes
+    /// give more regular blinks.
+    pub gamma_shape: u32,
+
+    /// The average delay between blinks, in seconds. Ignored by the uniform distribution, whose
+    /// average is halfway between the shortest and longest delays.
+    pub mean_delay_secs: f32,
+
+    /// The shortest delay between blinks, in seconds.
+    pub min_delay_secs: f32,
+
+    /// The longest delay between blinks, in seconds.
+    pub max_delay_secs: f32,
+
+    /// The chance that a blink is followed by a second one right away, from 0 to 1.
+    pub double_blink_chance: f32,
+
+    /// The time between the two blinks of a double blink, in milliseconds.
+    pub double_blink_gap_ms: f32,
+
+    /// The chance that the character blinks when it stops talking, from 0 to 1. People tend to
+    /// blink at pauses in speech.
+    pub pause_blink_chance: f32,
+
+    /// Different delays between blinks for specific eyes expressions, such as slower blinks for
+    /// sleepy eyes. Settings left out are taken from the settings above.
+    pub expressions: HashMap<String, BlinkDelayOverrides>,
+}
+
+impl Default for BlinkConfig {
+    fn default() -> Self {
+        Self {
+            distribution: Default::default(),
+            gamma_shape: 3,
+            mean_delay_secs: 3.0,
+            min_delay_secs: 1.0,
+            max_delay_secs: 5.0,
+            double_blink_chance: 0.15,
+            double_blink_gap_ms: 80.0,
+            pause_blink_chance: 0.4,
+            expressions: HashMap::new(),
+        }
+    }
+}
+
+impl BlinkConfig {
+    /// Returns the settings for the time between blinks with the given expression.
+    fn delay(&self, expression_name: &str) -> BlinkDelay {
+        let overrides = self.expressions.get(expression_name);
+        let pick = |value: fn(&BlinkDelayOverrides) -> Option<f32>, default: f32| {
+            overrides.and_then(value).unwrap_or(default)
+        };
+
+        BlinkDelay {
+            distribution: overrides
+                .and_then(|o| o.distribution)
+                .unwrap_or(self.distribution),
+            gamma_shape: overrides
+                .and_then(|o| o.gamma_shape)
+                .unwrap_or(self.gamma_shape),
+            mean_delay_secs: pick(|o| o.mean_delay_secs, self.mean_delay_secs),
+            min_delay_secs: pick(|o| o.min_delay_secs, self.min_delay_secs),
+            max_delay_secs: pick(|o| o.max_delay_secs, self.max_delay_secs),
+            double_blink_chance: pick(|o| o.double_blink_chance, self.double_blink_chance),
+        }
+    }
+}
+
+/// How the delays between blinks are picked. Whatever the distribution, delays stay between the
+/// shortest and longest delays.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlinkDistribution {
+    /// Any delay between the shortest and longest is as likely as any other.
+    #[default]
+    Uniform,
+
+    /// Blinks come at random like raindrops, so short delays are the most likely, with the
+    /// occasional long stare.
+    Poisson,
+
+    /// Delays cluster around the average, which is closest to how people blink.
+    Gamma,
+}
+
+/// Per-expression changes to the time between blinks. Each setting replaces the one of the same
+/// name in `BlinkConfig`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlinkDelayOverrides {
+    pub distribution: Option<BlinkDistribution>,
+    pub gamma_shape: Option<u32>,
+    pub mean_delay_secs: Option<f32>,
+    pub min_delay_secs: Option<f32>,
+    pub max_delay_secs: Option<f32>,
+    pub double_blink_chance: Option<f32>,
+}
+
+/// The settings for the time between blinks with a specific expression.
+struct BlinkDelay {
+    distribution: BlinkDistribution,
+    gamma_shape: u32,
+    mean_delay_secs: f32,
+    min_delay_secs: f32,
+    max_delay_secs: f32,
+    double_blink_chance: f32,
+}
+
+impl BlinkDelay {
+    /// Returns a random delay until the next blink.
+    fn sample(&self) -> Duration {
+        let min = self.min_delay_secs.max(0.0);
+        let max = self.max_delay_secs.max(min);
+        let delay = match self.distribution {
+            BlinkDistribution::Uniform => min + rand::random::<f32>() * (max - min),
+            BlinkDistribution::Poisson => random_gamma(1, self.mean_delay_secs),
+            BlinkDistribution::Gamma => random_gamma(self.gamma_shape.max(1), self.mean_delay_secs),
+        };
+        Duration::from_secs_f32(delay.clamp(min, max))
+    }
+}
+
+/// Returns a random number from the gamma distribution with the given whole-number shape and
+/// mean, as the sum of `shape` exponentially distributed numbers.
+fn random_gamma(shape: u32, mean: f32) -> f32 {
+    let scale = mean.max(0.0) / shape as f32;
+    (0..shape)
+        .map(|_| -(1.0 - rand::random::<f32>()).ln() * scale)
+        .sum()
+}
+
+/// Decides when the eyes blink.
+pub struct BlinkTimer {
+    config: BlinkConfig,
+
+    /// The time at which the last blink started.
+    last_blink: Instant,
+
+    /// The duration from the start of the last blink until the next blink.
+    next_blink_time: Duration,
+
+    /// Whether the last blink was the first of a double blink.
+    double_blinking: bool,
+
+    /// Whether the character was talking in the last update.
+    was_speaking: bool,
+}
+
+impl BlinkTimer {
+    pub fn new(config: &BlinkConfig, now: Instant) -> Self {
+        Self {
+            next_blink_time: config.delay("").sample(),
+            config: config.clone(),
+            last_blink: now,
+            double_blinking: false,
+            was_speaking: false,
+        }
+    }
+
+    /// Returns the time at which the last blink started.
+    pub fn last_blink(&self) -> Instant {
+        self.last_blink
+    }
+
+    /// Starts a blink right away.
+    pub fn blink_now(&mut self, now: Instant) {
+        self.last_blink = now;
+    }
+
+    /// Blinks if it's time to. The delay until the next blink is picked with the settings for the
+    /// given expression, whose blink takes `blink_duration`, and the character may blink when it
+    /// stops talking.
+    pub fn update(
+        &mut self,
+        expression_name: &str,
+        blink_duration: Duration,
+        speaking: bool,
+        now: Instant,
+    ) {
+        let since_blink = now.duration_since(self.last_blink);
+
+        // blink at a pause in speech, unless the last blink was very recent
+        let delay = self.config.delay(expression_name);
+        let min_delay = Duration::from_secs_f32(delay.min_delay_secs.max(0.0));
+        if self.was_speaking
+            && !speaking
+            && since_blink >= min_delay
+            && rand::random::<f32>() < self.config.pause_blink_chance
+        {
+            self.next_blink_time = since_blink;
+        }
+        self.was_speaking = speaking;
+
+        // if the time now has passed the next blink time, blink. set last_blink to now and
+        // next_blink to some random delay, or to right after this blink for a double blink.
+        if now >= self.last_blink + self.next_blink_time {
+            self.last_blink = now;
+            self.double_blinking =
+                !self.double_blinking && rand::random::<f32>() < delay.double_blink_chance;
+            self.next_blink_time = if self.double_blinking {
+                blink_duration
+                    + Duration::from_secs_f32(self.config.double_blink_gap_ms.max(0.0) / 1000.0)
+            } else {
+                delay.sample()
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns settings that blink exactly every `delay_secs`, never by chance.
+    fn config(delay_secs: f32) -> BlinkConfig {
+        BlinkConfig {
+            min_delay_secs: delay_secs,
+            max_delay_secs: delay_secs,
+            double_blink_chance: 0.0,
+            pause_blink_chance: 0.0,
+            ..Default::default()
+        }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// How long the blinks in these tests take.
+    const BLINK: Duration = Duration::from_millis(200);
+
+    /// Updates the timer `t` milliseconds after `start`, and returns when the last blink started,
+    /// in milliseconds after `start`.
+    fn blink_at(timer: &mut BlinkTimer, start: Instant, speaking: bool, t: u64) -> u128 {
+        timer.update("talking", BLINK, speaking, start + ms(t));
+        timer.last_blink().duration_since(start).as_millis()
+    }
+
+    #[test]
+    fn blinks_after_the_delay() {
+        let start = Instant::now();
+        let mut timer = BlinkTimer::new(&config(1.0), start);
+        assert_eq!(blink_at(&mut timer, start, false, 999), 0);
+        assert_eq!(blink_at(&mut timer, start, false, 1000), 1000);
+        assert_eq!(blink_at(&mut timer, start, false, 1999), 1000);
+        assert_eq!(blink_at(&mut timer, start, false, 2001), 2001);
+    }
+
+    #[test]
+    fn pause_in_speech_blinks() {
+        let start = Instant::now();
+        // blinks on their own only come every 10 seconds, but pauses may blink after 1
+        let config = BlinkConfig {
+            pause_blink_chance: 1.0,
+            expressions: HashMap::from([(
+                "talking".to_string(),
+                BlinkDelayOverrides {
+                    min_delay_secs: Some(1.0),
+                    ..Default::default()
+                },
+            )]),
+            ..config(10.0)
+        };
+        let mut timer = BlinkTimer::new(&config, start);
+
+        // too soon after the last blink for a pause to count
+        blink_at(&mut timer, start, true, 500);
+        assert_eq!(blink_at(&mut timer, start, false, 600), 0);
+
+        blink_at(&mut timer, start, true, 2000);
+        assert_eq!(blink_at(&mut timer, start, false, 2100), 2100);
+    }
+
+    #[test]
+    fn double_blink_follows_right_after_the_first() {
+        let start = Instant::now();
+        let config = BlinkConfig {
+            double_blink_chance: 1.0,
+            ..config(1.0)
+        };
+        let mut timer = BlinkTimer::new(&config, start);
+        assert_eq!(blink_at(&mut timer, start, false, 1000), 1000);
+
+        // the second blink starts after the first one and the gap
+        assert_eq!(blink_at(&mut timer, start, false, 1279), 1000);
+        assert_eq!(blink_at(&mut timer, start, false, 1281), 1281);
+
+        // and isn't followed by a third
+        assert_eq!(blink_at(&mut timer, start, false, 2280), 1281);
+        assert_eq!(blink_at(&mut timer, start, false, 2282), 2282);
+    }
+
+    #[test]
+    fn delays_stay_between_the_shortest_and_longest() {
+        for (distribution, mean_delay_secs) in [
+            (BlinkDistribution::Uniform, 3.0),
+            (BlinkDistribution::Poisson, 0.0),
+            (BlinkDistribution::Poisson, 100.0),
+            (BlinkDistribution::Gamma, 0.0),
+            (BlinkDistribution::Gamma, 100.0),
+        ] {
+            let config = BlinkConfig {
+                distribution,
+                mean_delay_secs,
+                ..Default::default()
+            };
+            let delay = config.delay("");
+            for _ in 0..100 {
+                let secs = delay.sample().as_secs_f32();
+                assert!(
+                    (1.0..=5.0).contains(&secs),
+                    "{distribution:?} gave {secs} s"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::time::{Duration, Instant};
+
+use eframe::egui::Vec2;
+use serde::Deserialize;
+
+/// Settings for where the character looks.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GazeConfig {
+    /// How far the pupils can move from the center of the eyes, across and down, in points.
+    pub range_px: [f32; 2],
+
+    /// Whether the character looks at the mouse cursor while it's over the window.
+    pub follow_cursor: bool,
+
+    /// Whether the character glances around on its own when nothing else draws its gaze.
+    pub saccades: bool,
+
+    /// How far the character glances around on its own, from 0 to 1 of the range.
+    pub saccade_range: f32,
+
+    /// The shortest time between glances, in milliseconds.
+    pub saccade_min_ms: f32,
+
+    /// The longest time between glances, in milliseconds.
+    pub saccade_max_ms: f32,
+
+    /// How quickly the pupils move to where the character looks, in milliseconds.
+    pub smoothing_ms: f32,
+}
+
+impl Default for GazeConfig {
+    fn default() -> Self {
+        Self {
+            range_px: [12.0, 6.0],
+            follow_cursor: true,
+            saccades: true,
+            saccade_range: 0.5,
+            saccade_min_ms: 600.0,
+            saccade_max_ms: 3000.0,
+            smoothing_ms: 60.0,
+        }
+    }
+}
+
+/// Tracks where the character looks. Directions are given from -1 to 1 across and down the
+/// range of the pupils.
+pub struct Gaze {
+    config: GazeConfig,
+
+    /// Where the pupils are looking now.
+    direction: Vec2,
+
+    /// Where the character glances on its own.
+    saccade_target: Vec2,
+
+    /// The time at which the character glances somewhere else.
+    next_saccade: Instant,
+
+    /// The time of the last update.
+    last_update: Instant,
+}
+
+impl Gaze {
+    pub fn new(config: &GazeConfig, now: Instant) -> Self {
+        Self {
+            config: *config,
+            direction: Vec2::ZERO,
+            saccade_target: Vec2::ZERO,
+            next_saccade: now,
+            last_update: now,
+        }
+    }
+
+    /// Moves the pupils towards where the character should look and returns how far they're
+    /// moved from the center, in points. A look from a hotkey wins over the cursor, which wins
+    /// over glancing around.
+    pub fn update(&mut self, look: Option<Vec2>, cursor: Option<Vec2>, now: Instant) -> Vec2 {
+        if now >= self.next_saccade {
+            // glance somewhere random, coming back to the middle every now and then
+            self.saccade_target = if rand::random::<f32>() < 0.3 {
+                Vec2::ZERO
+            } else {
+                Vec2::new(
+                    rand::random::<f32>() * 2.0 - 1.0,
+                    rand::random::<f32>() * 2.0 - 1.0,
+                ) * self.config.saccade_range
+            };
+
+            let min = self.config.saccade_min_ms.max(0.0);
+            let max = self.config.saccade_max_ms.max(min);
+            self.next_saccade =
+                now + Duration::from_secs_f32((min + rand::random::<f32>() * (max - min)) / 1000.0);
+        }
+
+        let target = look
+            .or(cursor.filter(|_| self.config.follow_cursor))
+            .or(self.config.saccades.then_some(self.saccade_target))
+            .unwrap_or(Vec2::ZERO);
+
+        // keep the pupils within an ellipse spanning the range
+        let target = if target.length() > 1.0 {
+            target.normalized()
+        } else {
+            target
+        };
+
+        let secs = now.duration_since(self.last_update).as_secs_f32();
+        self.last_update = now;
+        let coeff = 1.0 - (-secs * 1000.0 / self.config.smoothing_ms.max(f32::EPSILON)).exp();
+        self.direction += (target - self.direction) * coeff;
+
+        self.direction * Vec2::from(self.config.range_px)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns settings where the pupils move 10 points each way and glances stay in the middle.
+    fn config(smoothing_ms: f32) -> GazeConfig {
+        GazeConfig {
+            range_px: [10.0, 10.0],
+            saccade_range: 0.0,
+            smoothing_ms,
+            ..Default::default()
+        }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn assert_near(actual: Vec2, expected: Vec2) {
+        assert!(
+            (actual - expected).length() < 1e-2,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    #[test]
+    fn hotkey_beats_cursor_beats_glancing() {
+        let start = Instant::now();
+        let mut gaze = Gaze::new(&config(0.0), start);
+        let look = Some(Vec2::new(1.0, 0.0));
+        let cursor = Some(Vec2::new(0.0, -0.5));
+        assert_near(
+            gaze.update(look, cursor, start + ms(100)),
+            Vec2::new(10.0, 0.0),
+        );
+        assert_near(
+            gaze.update(None, cursor, start + ms(200)),
+            Vec2::new(0.0, -5.0),
+        );
+        assert_near(gaze.update(None, None, start + ms(300)), Vec2::ZERO);
+    }
+
+    #[test]
+    fn cursor_is_ignored_unless_followed() {
+        let start = Instant::now();
+        let config = GazeConfig {
+            follow_cursor: false,
+            ..config(0.0)
+        };
+        let mut gaze = Gaze::new(&config, start);
+        let cursor = Some(Vec2::new(0.0, -0.5));
+        assert_near(gaze.update(None, cursor, start + ms(100)), Vec2::ZERO);
+    }
+
+    #[test]
+    fn gaze_stays_within_the_range() {
+        let start = Instant::now();
+        let mut gaze = Gaze::new(&config(0.0), start);
+        let look = Some(Vec2::new(3.0, 4.0));
+        assert_near(
+            gaze.update(look, None, start + ms(100)),
+            Vec2::new(6.0, 8.0),
+        );
+    }
+
+    #[test]
+    fn pupils_move_smoothly_to_the_target() {
+        let start = Instant::now();
+        let mut gaze = Gaze::new(&config(100.0), start);
+        let look = Some(Vec2::new(1.0, 0.0));
+
+        // after one smoothing time, the pupils have covered about 63% of the way
+        let offset = gaze.update(look, None, start + ms(100));
+        assert_near(offset, Vec2::new(10.0 * (1.0 - (-1.0f32).exp()), 0.0));
+
+        // and they get there in the end
+        assert_near(
+            gaze.update(look, None, start + ms(2000)),
+            Vec2::new(10.0, 0.0),
+        );
+    }
+}
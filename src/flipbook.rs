@@ -0,0 +1,161 @@
+use std::{
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use eframe::egui::Image;
+
+/// The minimum time a frame must be visible.
+pub const MINIMUM_FRAME_TIME: Duration = Duration::from_millis(1000 / 24);
+
+/// A sequence of images shown one after the other, like a classic PNGtuber's talking frames. A
+/// single image is a flipbook with one frame.
+pub struct Flipbook<'a> {
+    /// The frames and how long each is shown.
+    frames: Vec<(Image<'a>, Duration)>,
+
+    /// The order frames are shown in during one cycle, as indices into `frames`.
+    sequence: Vec<usize>,
+
+    /// The length of one cycle.
+    cycle: Duration,
+}
+
+impl<'a> Flipbook<'a> {
+    /// Creates a flipbook from frames and their durations. After the last frame, the flipbook
+    /// starts over from the first one, or plays backwards to it if `ping_pong` is set. Frames are
+    /// shown for at least `MINIMUM_FRAME_TIME`, however short their duration.
+    pub fn new(frames: Vec<(Image<'a>, Duration)>, ping_pong: bool) -> Result<Self, NoFrames> {
+        if frames.is_empty() {
+            return Err(NoFrames);
+        }
+
+        let frames: Vec<_> = frames
+            .into_iter()
+            .map(|(image, duration)| (image, duration.max(MINIMUM_FRAME_TIME)))
+            .collect();
+
+        let mut sequence: Vec<_> = (0..frames.len()).collect();
+        if ping_pong {
+            // the first and last frames aren't repeated at the turning points
+            sequence.extend((1..frames.len().saturating_sub(1)).rev());
+        }
+        let cycle = sequence.iter().map(|&i| frames[i].1).sum();
+
+        Ok(Self {
+            frames,
+            sequence,
+            cycle,
+        })
+    }
+
+    /// Creates a flipbook that always shows the same image.
+    pub fn still(image: Image<'a>) -> Self {
+        Self::new(vec![(image, MINIMUM_FRAME_TIME)], false).expect("a still has one frame")
+    }
+
+    /// Returns the frame to show the given time after the flipbook started playing.
+    pub fn frame_at(&self, elapsed: Duration) -> &Image<'a> {
+        if self.frames.len() == 1 {
+            return &self.frames[0].0;
+        }
+
+        let mut remaining =
+            Duration::from_nanos((elapsed.as_nanos() % self.cycle.as_nanos().max(1)) as u64);
+        for &index in &self.sequence {
+            let (image, duration) = &self.frames[index];
+            if remaining < *duration {
+                return image;
+            }
+            remaining -= *duration;
+        }
+
+        &self.frames[self.sequence[0]].0
+    }
+}
+
+/// The error returned when a flipbook is created without any frames.
+#[derive(Debug)]
+pub struct NoFrames;
+
+impl Display for NoFrames {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a flipbook needs at least one frame")
+    }
+}
+
+impl std::error::Error for NoFrames {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn flipbook(durations: &[u64], ping_pong: bool) -> Flipbook<'static> {
+        let frames = durations
+            .iter()
+            .enumerate()
+            .map(|(i, &d)| (Image::from_uri(i.to_string()), ms(d)))
+            .collect();
+        Flipbook::new(frames, ping_pong).unwrap()
+    }
+
+    /// The frames shown every 100 ms, starting at 50 ms.
+    fn shown(flipbook: &Flipbook, count: u64) -> Vec<String> {
+        (0..count)
+            .map(|i| {
+                flipbook
+                    .frame_at(ms(50 + i * 100))
+                    .uri()
+                    .unwrap()
+                    .to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frames_play_in_order_and_start_over() {
+        let flipbook = flipbook(&[100, 100, 100], false);
+        assert_eq!(shown(&flipbook, 7), ["0", "1", "2", "0", "1", "2", "0"]);
+    }
+
+    #[test]
+    fn ping_pong_does_not_repeat_the_end_frames() {
+        let flipbook = flipbook(&[100, 100, 100], true);
+        assert_eq!(
+            shown(&flipbook, 9),
+            ["0", "1", "2", "1", "0", "1", "2", "1", "0"]
+        );
+    }
+
+    #[test]
+    fn ping_pong_with_two_frames_alternates() {
+        let flipbook = flipbook(&[100, 100], true);
+        assert_eq!(shown(&flipbook, 4), ["0", "1", "0", "1"]);
+    }
+
+    #[test]
+    fn short_frames_last_the_minimum_frame_time() {
+        let flipbook = flipbook(&[1, 100], false);
+        let first = |elapsed| flipbook.frame_at(elapsed).uri() == Some("0");
+        assert!(first(MINIMUM_FRAME_TIME - ms(1)));
+        assert!(!first(MINIMUM_FRAME_TIME + ms(1)));
+        assert!(first(MINIMUM_FRAME_TIME + ms(101)));
+    }
+
+    #[test]
+    fn wraps_around_after_many_cycles() {
+        let flipbook = flipbook(&[100, 200], false);
+        let cycles = 1_000_000;
+        assert_eq!(flipbook.frame_at(ms(300 * cycles + 50)).uri(), Some("0"));
+        assert_eq!(flipbook.frame_at(ms(300 * cycles + 150)).uri(), Some("1"));
+    }
+
+    #[test]
+    fn no_frames_is_an_error() {
+        assert!(Flipbook::new(Vec::new(), false).is_err());
+    }
+}
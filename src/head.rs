@@ -1,3 +1,6 @@
+mod motion;
+mod phase;
+
 use std::{
     collections::HashMap,
     time::{Duration, Instant},
@@ -5,23 +8,59 @@ use std::{
 
 use eframe::{
     egui::{Image, Ui},
-    epaint::Rect,
+    epaint::{Color32, Rect},
+};
+
+use crate::{
+    POP_DURATION,
+    audio::{AnalysisFrame, IntensityThresholds, Viseme},
+    crossfade::{Crossfade, CrossfadeConfig, ImageStyle},
+    flipbook::{Flipbook, MINIMUM_FRAME_TIME},
+    threshold::Threshold,
 };
+use motion::HeadMotion;
+pub use motion::HeadMotionConfig;
+use phase::PhaseSelector;
+pub use phase::PhaseTiming;
 
-use crate::POP_DURATION;
+/// How far below a volume threshold the level must fall for the mouth to close again, in dB.
+pub const THRESHOLD_HYSTERESIS_DB: f32 = 4.0;
 
-/// The minimum time a speaking frame must be visible.
-const MINIMUM_FRAME_TIME: Duration = Duration::from_millis(1000 / 24);
+/// The openness of the yell threshold. Mouth levels from here up count as yelling.
+const YELL_OPENNESS: f32 = 1.0;
 
-pub struct Head<'a> {
+/// The volume levels at which the character's mouth changes shape. These anchor the openness
+/// scale that mouth levels are defined on: the half speak, full speak and yell thresholds are at
+/// an openness of 1/3, 2/3 and 1. Their exit levels decide where the mouth closes again.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeakThresholds {
     /// The threshold at which the character is considered to be half speaking, in dBFS.
-    half_speak_threshold_dbfs: f32,
+    pub half_speak_dbfs: Threshold,
 
     /// The threshold at which the character is considered to be fully speaking, in dBFS.
-    full_speak_threshold_dbfs: f32,
+    pub full_speak_dbfs: Threshold,
 
     /// The threshold at which the character is considered to be yelling, in dBFS.
-    yelling_threshold_dbfs: f32,
+    pub yell_dbfs: Threshold,
+}
+
+impl Default for SpeakThresholds {
+    fn default() -> Self {
+        Self {
+            half_speak_dbfs: Threshold::with_hysteresis(-52.0, THRESHOLD_HYSTERESIS_DB),
+            full_speak_dbfs: Threshold::with_hysteresis(-38.0, THRESHOLD_HYSTERESIS_DB),
+            yell_dbfs: Threshold::with_hysteresis(-15.0, THRESHOLD_HYSTERESIS_DB),
+        }
+    }
+}
+
+pub struct Head<'a> {
+    /// The volume levels at which the speaking phase changes.
+    thresholds: SpeakThresholds,
+
+    /// The speech intensities at which the speaking phase changes, used instead of `thresholds`
+    /// for frames with a speech intensity.
+    intensity_thresholds: IntensityThresholds,
 
     /// Base images to use for the character's head.
     expressions: HashMap<String, HeadExpression<'a>>,
@@ -29,193 +68,545 @@ pub struct Head<'a> {
     /// The expression to use when an expression is not found.
     default_expression: HeadExpression<'a>,
 
-    /// The current speaking phase.
-    speak_phase: SpeakPhase,
-
-    /// The previous speaking phase.
-    last_speak_phase: SpeakPhase,
-
-    /// The time at which the current speaking phase started.
-    last_phase_change: Instant,
+    /// Picks the speaking phase from the level.
+    phase: PhaseSelector,
 
     /// The time at which speaking last started (phase went from silent to not silent)
     last_speak_start: Instant,
+
+    /// The last time the mouth was open.
+    last_open: Instant,
+
+    /// The time at which yelling last started, if the character has yelled yet.
+    last_yell_start: Option<Instant>,
+
+    /// Whether the mouth was at a yelling level in the last frame.
+    was_yelling: bool,
+
+    /// The expression used in the last frame.
+    last_expression_name: String,
+
+    /// Fades between images when the expression or speaking phase changes.
+    crossfade: Crossfade<'a>,
+
+    /// Bobs and tilts the head while talking.
+    motion: HeadMotion,
 }
 
-impl Head<'_> {
-    pub fn paint(&mut self, ui: &mut Ui, rect: Rect, volume: f32, expression_name: &str) {
-        // determine head_base to use
-        if self.last_phase_change.elapsed() > MINIMUM_FRAME_TIME {
-            self.last_speak_phase = self.speak_phase;
-            self.speak_phase = if volume > self.yelling_threshold_dbfs {
-                SpeakPhase::Yell
-            } else if volume > self.full_speak_threshold_dbfs {
-                SpeakPhase::FullSpeak
-            } else if volume > self.half_speak_threshold_dbfs {
-                SpeakPhase::HalfSpeak
-            } else {
-                SpeakPhase::Quiet
-            };
-
-            if self.last_speak_phase != self.speak_phase {
-                self.last_phase_change = Instant::now();
-            }
-        }
+impl<'a> Head<'a> {
+    /// Paints the head over the given rectangle, with the mouth following the given audio frame.
+    /// If the frame doesn't sound like speech, the mouth closes no matter how loud it is. While
+    /// speaking, the image for the frame's viseme is used if the expression has one. The head is
+    /// tinted with the given color, and bobs and tilts on its own while talking. Returns where
+    /// the head was painted and how it was rotated, so that the eyes can follow it.
+    pub fn paint(
+        &mut self,
+        ui: &mut Ui,
+        rect: Rect,
+        frame: &AnalysisFrame,
+        expression_name: &str,
+        tint: Color32,
+    ) -> (Rect, ImageStyle) {
+        let now = Instant::now();
+        let expression = self
+            .expressions
+            .get(expression_name)
+            .unwrap_or(&self.default_expression);
 
-        if self.last_speak_phase == SpeakPhase::Quiet
-            && self.speak_phase != SpeakPhase::Quiet
-            && self.last_speak_start.elapsed().as_secs_f32() > POP_DURATION
+        let previous_level = self.phase.phase();
+        let was_quiet = previous_level == 0;
+        let (input, anchors) = self.input(frame);
+        let (level, openness) = mouth_level(
+            &mut self.phase,
+            frame.is_speech.then_some(input),
+            anchors,
+            &expression.thresholds,
+            now,
+        );
+
+        if was_quiet
+            && level != 0
+            && now.duration_since(self.last_speak_start).as_secs_f32() > POP_DURATION
         {
-            self.last_speak_start = Instant::now();
+            self.last_speak_start = now;
         }
 
-        let head_base = self
-            .expressions
-            .get(expression_name)
-            .unwrap_or(&self.default_expression)
-            .get_image(self.speak_phase);
+        if level != 0 {
+            self.last_open = now;
+        }
 
-        head_base.paint_at(ui, rect);
+        let is_yelling = level > 0 && expression.thresholds[level - 1].enter >= YELL_OPENNESS;
+        if is_yelling && !self.was_yelling {
+            self.last_yell_start = Some(now);
+        }
+        self.was_yelling = is_yelling;
+
+        let changed = level != previous_level || expression_name != self.last_expression_name;
+        if expression_name != self.last_expression_name {
+            self.last_expression_name = expression_name.to_string();
+        }
+
+        let pose = self.motion.update(
+            openness.unwrap_or(0.0),
+            level != 0,
+            level > previous_level,
+            now,
+        );
+        let rect = rect.translate(pose.offset);
+        let style = ImageStyle {
+            tint,
+            angle: pose.angle,
+            pivot: pose.pivot,
+        };
+
+        let elapsed = now.duration_since(self.phase.changed_at());
+        let image = expression.get_image(level, frame.viseme, elapsed);
+        self.crossfade.paint(ui, rect, image, style, changed, now);
+
+        (rect, style)
+    }
+
+    /// Returns the level the mouth follows for the given frame, along with the half speak, full
+    /// speak and yell thresholds for it. Frames with a speech intensity use the intensity and the
+    /// intensity thresholds, the others the level in dBFS and the volume thresholds.
+    fn input(&self, frame: &AnalysisFrame) -> (f32, [Threshold; 3]) {
+        match frame.intensity {
+            Some(intensity) => (
+                intensity,
+                [
+                    self.intensity_thresholds.half_speak,
+                    self.intensity_thresholds.full_speak,
+                    self.intensity_thresholds.yell,
+                ],
+            ),
+            None => (
+                frame.level_dbfs,
+                [
+                    self.thresholds.half_speak_dbfs,
+                    self.thresholds.full_speak_dbfs,
+                    self.thresholds.yell_dbfs,
+                ],
+            ),
+        }
+    }
+
+    /// Returns the names of the head's expressions.
+    pub fn expression_names(&self) -> impl Iterator<Item = &str> {
+        self.expressions.keys().map(String::as_str)
+    }
+
+    pub fn has_expression(&self, name: &str) -> bool {
+        self.expressions.contains_key(name)
+    }
+
+    /// Returns whether the given expression has any images for an open mouth. Without any, the
+    /// mouth never moves.
+    pub fn has_mouth_images(&self, name: &str) -> bool {
+        self.expressions.get(name).is_some_and(|expression| {
+            expression.levels.iter().any(|level| level.image.is_some())
+                || expression.visemes.keys().any(|&v| v != Viseme::Closed)
+        })
     }
 
     pub fn get_last_speak_start(&self) -> &Instant {
         &self.last_speak_start
     }
+
+    /// Returns whether the mouth was open in the last frame painted.
+    pub fn is_mouth_open(&self) -> bool {
+        self.phase.phase() != 0
+    }
+
+    pub fn get_last_open(&self) -> Instant {
+        self.last_open
+    }
+
+    pub fn get_last_yell_start(&self) -> Option<Instant> {
+        self.last_yell_start
+    }
+
+    pub fn set_thresholds(&mut self, thresholds: SpeakThresholds) {
+        self.thresholds = thresholds;
+    }
+
+    pub fn set_intensity_thresholds(&mut self, thresholds: IntensityThresholds) {
+        self.intensity_thresholds = thresholds;
+    }
+
+    pub fn set_phase_timing(&mut self, timing: &PhaseTiming) {
+        self.phase = PhaseSelector::new(timing, MINIMUM_FRAME_TIME, Instant::now());
+    }
+
+    pub fn set_crossfade(&mut self, config: &CrossfadeConfig) {
+        self.crossfade = Crossfade::new(config);
+    }
+
+    pub fn set_motion(&mut self, config: &HeadMotionConfig) {
+        self.motion = HeadMotion::new(config, Instant::now());
+    }
 }
 
 impl Default for Head<'_> {
     fn default() -> Self {
         Self {
-            half_speak_threshold_dbfs: -46.0,
-            full_speak_threshold_dbfs: -30.0,
-            yelling_threshold_dbfs: -5.0,
+            thresholds: Default::default(),
+            intensity_thresholds: Default::default(),
 
             expressions: HashMap::from([
                 (
                     "happy".to_string(),
-                    HeadExpression {
-                        idle: Image::from_bytes(
+                    closed_lips(HeadExpression::with_speak_images(
+                        Image::from_bytes(
                             "bytes://head_happy_quiet",
                             include_bytes!("assets/head_happy_quiet.png"),
                         ),
-                        half_speak: Some(Image::from_bytes(
+                        Some(Image::from_bytes(
                             "bytes://head_happy_halfspeak",
                             include_bytes!("assets/head_happy_halfspeak.png"),
                         )),
-                        full_speak: Some(Image::from_bytes(
+                        Some(Image::from_bytes(
                             "bytes://head_happy_speak",
                             include_bytes!("assets/head_happy_speak.png"),
                         )),
-                        yell: None,
-                    },
+                        None,
+                    )),
                 ),
                 (
                     "frown".to_string(),
-                    HeadExpression {
-                        idle: Image::from_bytes(
+                    closed_lips(HeadExpression::with_speak_images(
+                        Image::from_bytes(
                             "bytes://head_frown_quiet",
                             include_bytes!("assets/head_frown_quiet.png"),
                         ),
-                        half_speak: Some(Image::from_bytes(
+                        Some(Image::from_bytes(
                             "bytes://head_frown_halfspeak",
                             include_bytes!("assets/head_frown_halfspeak.png"),
                         )),
-                        full_speak: Some(Image::from_bytes(
+                        Some(Image::from_bytes(
                             "bytes://head_frown_speak",
                             include_bytes!("assets/head_frown_speak.png"),
                         )),
-                        yell: Some(Image::from_bytes(
+                        Some(Image::from_bytes(
                             "bytes://head_frown_yell",
                             include_bytes!("assets/head_frown_yell.png"),
                         )),
-                    },
+                    )),
                 ),
                 (
                     "wavy".to_string(),
-                    HeadExpression {
-                        idle: Image::from_bytes(
+                    closed_lips(HeadExpression::with_speak_images(
+                        Image::from_bytes(
                             "bytes://head_wavy_quiet",
                             include_bytes!("assets/head_wavy_quiet.png"),
                         ),
-                        half_speak: Some(Image::from_bytes(
+                        Some(Image::from_bytes(
                             "bytes://head_wavy_halfspeak",
                             include_bytes!("assets/head_wavy_halfspeak.png"),
                         )),
-                        full_speak: Some(Image::from_bytes(
+                        Some(Image::from_bytes(
                             "bytes://head_wavy_speak",
                             include_bytes!("assets/head_wavy_speak.png"),
                         )),
-                        yell: Some(Image::from_bytes(
+                        Some(Image::from_bytes(
                             "bytes://head_wavy_yell",
                             include_bytes!("assets/head_wavy_yell.png"),
                         )),
-                    },
+                    )),
                 ),
             ]),
 
-            default_expression: HeadExpression {
-                idle: Image::from_bytes(
+            default_expression: closed_lips(HeadExpression::with_speak_images(
+                Image::from_bytes(
                     "bytes://head_default_quiet",
                     include_bytes!("assets/head_happy_quiet.png"),
                 ),
-                half_speak: Some(Image::from_bytes(
+                Some(Image::from_bytes(
                     "bytes://head_default_halfspeak",
                     include_bytes!("assets/head_happy_halfspeak.png"),
                 )),
-                full_speak: Some(Image::from_bytes(
+                Some(Image::from_bytes(
                     "bytes://head_default_speak",
                     include_bytes!("assets/head_happy_speak.png"),
                 )),
-                yell: Some(Image::from_bytes(
+                Some(Image::from_bytes(
                     "bytes://head_default_yell",
                     include_bytes!("assets/head_happy_yell.png"),
                 )),
-            },
+            )),
 
-            speak_phase: SpeakPhase::Quiet,
-            last_speak_phase: SpeakPhase::Quiet,
-            last_phase_change: Instant::now(),
+            phase: PhaseSelector::new(&Default::default(), MINIMUM_FRAME_TIME, Instant::now()),
             last_speak_start: Instant::now(),
+            last_open: Instant::now(),
+            last_yell_start: None,
+            was_yelling: false,
+            last_expression_name: String::new(),
+            crossfade: Default::default(),
+            motion: HeadMotion::new(&Default::default(), Instant::now()),
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
-enum SpeakPhase {
-    Quiet,
-    HalfSpeak,
-    FullSpeak,
-    Yell,
+/// Shows the closed mouth of the given expression for closed-lip sounds such as M, B and P.
+fn closed_lips(expression: HeadExpression<'_>) -> HeadExpression<'_> {
+    let idle = expression.idle.frame_at(Duration::ZERO).clone();
+    expression.with_viseme(Viseme::Closed, idle)
+}
+
+/// Picks the mouth level for the given input, or for silence if there's no speech. `anchors` are
+/// the half speak, full speak and yell thresholds on the input's scale, and `levels` the
+/// expression's mouth level thresholds as openness. Returns the mouth level, and how far the mouth
+/// is open while speaking.
+fn mouth_level(
+    phase: &mut PhaseSelector,
+    input: Option<f32>,
+    anchors: [Threshold; 3],
+    levels: &[Threshold],
+    now: Instant,
+) -> (usize, Option<f32>) {
+    // place the expression's mouth levels on the scale of the input, opening at the enter levels
+    // of the anchoring thresholds and closing at their exit levels
+    let enter = anchors.map(|threshold| threshold.enter);
+    let exit = anchors.map(|threshold| threshold.exit);
+    let thresholds: Vec<_> = levels
+        .iter()
+        .map(|threshold| Threshold {
+            enter: level_at(threshold.enter, enter),
+            exit: level_at(threshold.exit, exit),
+        })
+        .collect();
+    let level = phase.update(input, &thresholds, now);
+    (level, input.map(|input| openness(input, enter)))
+}
+
+/// Maps a level onto the openness scale, where the given half speak, full speak and yell
+/// thresholds are at 1/3, 2/3 and 1. Levels in between are interpolated, and levels outside the
+/// thresholds are extrapolated from the nearest two.
+fn openness(level: f32, [half_speak, full_speak, yell]: [f32; 3]) -> f32 {
+    let (low, high, base) = if level < full_speak {
+        (half_speak, full_speak, 1.0)
+    } else {
+        (full_speak, yell, 2.0)
+    };
+
+    let span = (high - low).max(f32::EPSILON);
+    ((base + (level - low) / span) / 3.0).max(0.0)
+}
+
+/// Maps an openness back onto the scale of the given half speak, full speak and yell thresholds,
+/// undoing `openness`.
+fn level_at(openness: f32, [half_speak, full_speak, yell]: [f32; 3]) -> f32 {
+    let (low, high, base) = if openness < 2.0 / 3.0 {
+        (half_speak, full_speak, 1.0)
+    } else {
+        (full_speak, yell, 2.0)
+    };
+
+    let span = (high - low).max(f32::EPSILON);
+    low + (openness * 3.0 - base) * span
+}
+
+/// A mouth level of a head expression.
+pub struct MouthLevel<'a> {
+    /// The openness at which the mouth opens to this level, and below which it closes again. An
+    /// openness of 1/3 closes at the exit level of the half speak threshold, 2/3 at that of the
+    /// full speak threshold and so on, so the exit here only needs to add to that hysteresis.
+    threshold: Threshold,
+
+    /// The image or animation for this level. Levels without one use the image of the nearest
+    /// lower level that has one.
+    image: Option<Flipbook<'a>>,
+
+    /// Whether mouth shape images replace this level's image while speaking. Wide open levels
+    /// such as yelling usually keep their own image.
+    visemes: bool,
+}
+
+impl<'a> MouthLevel<'a> {
+    pub fn new(threshold: Threshold, image: Option<Flipbook<'a>>, visemes: bool) -> Self {
+        Self {
+            threshold,
+            image,
+            visemes,
+        }
+    }
 }
 
 pub struct HeadExpression<'a> {
-    idle: Image<'a>,
-    half_speak: Option<Image<'a>>,
-    full_speak: Option<Image<'a>>,
-    yell: Option<Image<'a>>,
+    /// The image or animation for a closed mouth.
+    idle: Flipbook<'a>,
+
+    /// Mouth levels from the least to the most open, sorted by threshold.
+    levels: Vec<MouthLevel<'a>>,
+
+    /// The thresholds of `levels`, in the same order.
+    thresholds: Vec<Threshold>,
+
+    /// Images for specific mouth shapes, used while speaking in place of the images of levels that
+    /// allow it. Shapes without an image fall back to the level images.
+    visemes: HashMap<Viseme, Image<'a>>,
 }
 
-impl HeadExpression<'_> {
-    fn get_image(&self, phase: SpeakPhase) -> &Image<'_> {
-        match phase {
-            SpeakPhase::Quiet => &self.idle,
-            SpeakPhase::HalfSpeak => self.get_half_speak_image(),
-            SpeakPhase::FullSpeak => self.get_full_speak_image(),
-            SpeakPhase::Yell => self.get_yell_image(),
+impl<'a> HeadExpression<'a> {
+    /// Creates an expression with the given mouth levels, which may be listed in any order.
+    pub fn new(idle: Flipbook<'a>, mut levels: Vec<MouthLevel<'a>>) -> Self {
+        levels.sort_by(|a, b| a.threshold.enter.total_cmp(&b.threshold.enter));
+        Self {
+            idle,
+            thresholds: levels.iter().map(|level| level.threshold).collect(),
+            levels,
+            visemes: HashMap::new(),
         }
     }
 
-    pub fn get_half_speak_image(&self) -> &Image<'_> {
-        self.half_speak.as_ref().unwrap_or(&self.idle)
+    /// Creates an expression with the classic half speak, full speak and yell levels.
+    pub fn with_speak_images(
+        idle: Image<'a>,
+        half_speak: Option<Image<'a>>,
+        full_speak: Option<Image<'a>>,
+        yell: Option<Image<'a>>,
+    ) -> Self {
+        // the speak thresholds already close the mouth a little below where it opens
+        let at = |openness| Threshold::with_hysteresis(openness, 0.0);
+        Self::new(
+            Flipbook::still(idle),
+            vec![
+                MouthLevel::new(at(1.0 / 3.0), half_speak.map(Flipbook::still), true),
+                MouthLevel::new(at(2.0 / 3.0), full_speak.map(Flipbook::still), true),
+                MouthLevel::new(at(1.0), yell.map(Flipbook::still), false),
+            ],
+        )
     }
 
-    pub fn get_full_speak_image(&self) -> &Image<'_> {
-        self.full_speak
-            .as_ref()
-            .unwrap_or(self.get_half_speak_image())
+    /// Adds an image for the given mouth shape.
+    pub fn with_viseme(mut self, viseme: Viseme, image: Image<'a>) -> Self {
+        self.visemes.insert(viseme, image);
+        self
     }
 
-    pub fn get_yell_image(&self) -> &Image<'_> {
-        self.yell.as_ref().unwrap_or(self.get_full_speak_image())
+    /// Returns the image for the given mouth level, counting from 0 for a closed mouth, the given
+    /// time after the mouth reached that level.
+    fn get_image(&self, level: usize, viseme: Option<Viseme>, elapsed: Duration) -> &Image<'a> {
+        // the levels up to and including the given one
+        let levels = &self.levels[..level.min(self.levels.len())];
+
+        let viseme_image = levels
+            .last()
+            .filter(|level| level.visemes)
+            .and(viseme)
+            .and_then(|v| self.visemes.get(&v));
+        if let Some(image) = viseme_image {
+            return image;
+        }
+
+        // fall back to the nearest lower level with an image
+        levels
+            .iter()
+            .rev()
+            .find_map(|level| level.image.as_ref())
+            .unwrap_or(&self.idle)
+            .frame_at(elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f32::consts::TAU, fs};
+
+    use super::*;
+    use crate::audio::{AudioConfig, analyze_file, write_test_wav};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn level_at_undoes_openness() {
+        let anchors = [-52.0, -38.0, -15.0];
+        for level in [-60.0, -52.0, -45.0, -38.0, -20.0, -15.0, -5.0] {
+            let back = level_at(openness(level, anchors), anchors);
+            assert!((back - level).abs() < 1e-3, "{level} came back as {back}");
+        }
+    }
+
+    #[test]
+    fn file_input_opens_and_closes_the_mouth() {
+        // half a second of silence, a second of a tone at -20 dBFS and a second and a half of
+        // silence for the level to fall again
+        let sample_rate = 48000;
+        let path = write_test_wav(
+            "mouth",
+            sample_rate,
+            (0..sample_rate * 3).map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                if (0.5..1.5).contains(&t) {
+                    0.1 * 2.0f32.sqrt() * (TAU * 220.0 * t).sin()
+                } else {
+                    0.0
+                }
+            }),
+        );
+        let frames = analyze_file(&path, &AudioConfig::default());
+        fs::remove_file(&path).ok();
+        let frames = frames.unwrap();
+
+        // the tone is between the full speak and yell thresholds, so the mouth opens to the
+        // expression's second level
+        let mut head = Head::default();
+        let start = Instant::now();
+        let levels: Vec<_> = frames
+            .iter()
+            .map(|frame| {
+                let (input, anchors) = head.input(frame);
+                let (level, _) = mouth_level(
+                    &mut head.phase,
+                    frame.is_speech.then_some(input),
+                    anchors,
+                    &head.default_expression.thresholds,
+                    start + frame.time,
+                );
+                (frame.time, level)
+            })
+            .collect();
+
+        let during = |from: f32, to: f32| {
+            levels
+                .iter()
+                .filter(move |(time, _)| (from..to).contains(&time.as_secs_f32()))
+                .map(|&(_, level)| level)
+        };
+        assert!(during(0.0, 0.5).all(|level| level == 0));
+        assert!(during(0.7, 1.5).all(|level| level == 2));
+        assert!(during(2.5, 3.0).all(|level| level == 0));
+    }
+
+    #[test]
+    fn mouth_levels_open_at_their_openness() {
+        let anchors = SpeakThresholds::default();
+        let anchors = [
+            anchors.half_speak_dbfs,
+            anchors.full_speak_dbfs,
+            anchors.yell_dbfs,
+        ];
+        let levels = [0.5, 1.0].map(|openness| Threshold::with_hysteresis(openness, 0.0));
+        let start = Instant::now();
+        let timing = PhaseTiming {
+            hold_ms: 0.0,
+            phase_hold_ms: Vec::new(),
+            release_ms: 0.0,
+        };
+        let mut phase = PhaseSelector::new(&timing, Duration::ZERO, start);
+
+        // an openness of 0.5 is halfway between the half speak and full speak thresholds
+        let (level, openness) = mouth_level(&mut phase, Some(-46.0), anchors, &levels, start);
+        assert_eq!(level, 0);
+        assert!((openness.unwrap() - 0.476).abs() < 1e-3);
+        let (level, openness) = mouth_level(&mut phase, Some(-44.0), anchors, &levels, start);
+        assert_eq!(level, 1);
+        assert!((openness.unwrap() - 0.524).abs() < 1e-3);
+
+        // without speech the mouth closes, and isn't open at all
+        let (level, openness) = mouth_level(&mut phase, None, anchors, &levels, start + ms(500));
+        assert_eq!(level, 0);
+        assert_eq!(openness, None);
     }
 }
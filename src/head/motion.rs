@@ -0,0 +1,137 @@
+use std::{
+    f32::consts::PI,
+    time::{Duration, Instant},
+};
+
+use eframe::egui::Vec2;
+use serde::Deserialize;
+
+/// Settings for how the head moves on its own while the character talks.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeadMotionConfig {
+    /// How far the head bobs up when the mouth opens further, in points. Wider openings bob
+    /// higher.
+    pub bob_px: f32,
+
+    /// How long a bob takes, in milliseconds.
+    pub bob_ms: f32,
+
+    /// How far the head tilts to either side while talking, in degrees.
+    pub tilt_degrees: f32,
+
+    /// How often the head picks a new tilt while talking, in milliseconds.
+    pub tilt_change_ms: f32,
+
+    /// How quickly the head moves towards its tilt, and back upright once the character is
+    /// quiet, in milliseconds.
+    pub settle_ms: f32,
+
+    /// The point the head tilts around, from 0 to 1 across and down the image.
+    pub pivot: [f32; 2],
+}
+
+impl Default for HeadMotionConfig {
+    fn default() -> Self {
+        Self {
+            bob_px: 4.0,
+            bob_ms: 160.0,
+            tilt_degrees: 2.0,
+            tilt_change_ms: 700.0,
+            settle_ms: 250.0,
+            pivot: [0.5, 0.6],
+        }
+    }
+}
+
+/// Where the head is moved to and how far it's tilted, relative to the body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeadPose {
+    /// How far to move the head.
+    pub offset: Vec2,
+
+    /// How far to tilt the head clockwise, in radians.
+    pub angle: f32,
+
+    /// The point to tilt the head around, from 0 to 1 across and down the image.
+    pub pivot: Vec2,
+}
+
+/// Bobs the head on syllables and tilts it a little while the character talks.
+pub struct HeadMotion {
+    config: HeadMotionConfig,
+
+    /// The time at which the last bob started, and how high it goes as a fraction of `bob_px`.
+    bob: Option<(Instant, f32)>,
+
+    /// The current tilt, in radians.
+    angle: f32,
+
+    /// The tilt the head is moving towards, in radians.
+    target_angle: f32,
+
+    /// The time at which the head picks a new tilt.
+    next_tilt: Instant,
+
+    /// The time of the last update.
+    last_update: Instant,
+}
+
+impl HeadMotion {
+    pub fn new(config: &HeadMotionConfig, now: Instant) -> Self {
+        Self {
+            config: *config,
+            bob: None,
+            angle: 0.0,
+            target_angle: 0.0,
+            next_tilt: now,
+            last_update: now,
+        }
+    }
+
+    /// Moves the head and returns its pose. `speaking` tells whether the mouth is open, and
+    /// `opened` whether it opened further since the last update, which starts a bob as high as
+    /// the given mouth openness.
+    pub fn update(
+        &mut self,
+        openness: f32,
+        speaking: bool,
+        opened: bool,
+        now: Instant,
+    ) -> HeadPose {
+        if opened {
+            self.bob = Some((now, openness.clamp(0.0, 1.0)));
+        }
+
+        // pick a new tilt every so often while talking, and straighten up when quiet
+        if !speaking {
+            self.target_angle = 0.0;
+        } else if now >= self.next_tilt {
+            let max_angle = self.config.tilt_degrees.to_radians();
+            self.target_angle = (rand::random::<f32>() * 2.0 - 1.0) * max_angle;
+            self.next_tilt =
+                now + Duration::from_secs_f32(self.config.tilt_change_ms.max(0.0) / 1000.0);
+        }
+
+        let secs = now.duration_since(self.last_update).as_secs_f32();
+        self.last_update = now;
+        let coeff = 1.0 - (-secs * 1000.0 / self.config.settle_ms.max(f32::EPSILON)).exp();
+        self.angle += (self.target_angle - self.angle) * coeff;
+
+        // a bob goes up and comes back down along half a sine wave
+        let bob_secs = self.config.bob_ms.max(0.0) / 1000.0;
+        let lift = match self.bob {
+            Some((start, height)) if now.duration_since(start).as_secs_f32() < bob_secs => {
+                let t = now.duration_since(start).as_secs_f32() / bob_secs;
+                (t * PI).sin() * height * self.config.bob_px
+            }
+            _ => 0.0,
+        };
+
+        HeadPose {
+            offset: Vec2::new(0.0, -lift),
+            angle: self.angle,
+            pivot: Vec2::from(self.config.pivot),
+        }
+    }
+}
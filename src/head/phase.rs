@@ -0,0 +1,227 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::threshold::Threshold;
+
+/// Settings for how quickly the mouth changes shape.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhaseTiming {
+    /// The shortest time the mouth keeps a shape before changing again, in milliseconds.
+    pub hold_ms: f32,
+
+    /// The shortest time the mouth keeps specific shapes, in milliseconds, starting with the
+    /// closed mouth and going up one mouth level at a time. Shapes past the end of the list are
+    /// held for `hold_ms`.
+    pub phase_hold_ms: Vec<f32>,
+
+    /// How long the mouth stays slightly open after speech stops, in milliseconds. This keeps the
+    /// mouth from snapping shut between words.
+    pub release_ms: f32,
+}
+
+impl Default for PhaseTiming {
+    fn default() -> Self {
+        Self {
+            hold_ms: 1000.0 / 24.0,
+            phase_hold_ms: Vec::new(),
+            release_ms: 120.0,
+        }
+    }
+}
+
+/// Picks how far the mouth is open from the input level. Phases are numbered from 0 for a closed
+/// mouth up to the number of thresholds, and phase `n` is entered once the level reaches the
+/// `n`th threshold, counting from 1.
+pub struct PhaseSelector {
+    /// The shortest time a phase is held.
+    hold: Duration,
+
+    /// The shortest time specific phases are held, starting with phase 0. Phases past the end use
+    /// `hold`.
+    phase_holds: Vec<Duration>,
+
+    /// How long the lowest open phase is held after the input falls silent.
+    release: Duration,
+
+    /// The current phase.
+    phase: usize,
+
+    /// The time at which the current phase started.
+    changed_at: Instant,
+
+    /// The time at which the level last fell below every threshold, while the release tail is
+    /// running.
+    silent_since: Option<Instant>,
+}
+
+impl PhaseSelector {
+    /// Creates a selector with the mouth closed. `min_hold` is the least time a phase is held,
+    /// no matter the timing settings.
+    pub fn new(timing: &PhaseTiming, min_hold: Duration, now: Instant) -> Self {
+        let hold = |ms: f32| Duration::from_secs_f32(ms.max(0.0) / 1000.0).max(min_hold);
+        Self {
+            hold: hold(timing.hold_ms),
+            phase_holds: timing.phase_hold_ms.iter().map(|&ms| hold(ms)).collect(),
+            release: Duration::from_secs_f32(timing.release_ms.max(0.0) / 1000.0),
+            phase: 0,
+            changed_at: now,
+            silent_since: None,
+        }
+    }
+
+    /// Returns the current phase.
+    pub fn phase(&self) -> usize {
+        self.phase
+    }
+
+    /// Returns the time at which the current phase started.
+    pub fn changed_at(&self) -> Instant {
+        self.changed_at
+    }
+
+    /// Moves to the phase for the given level, or towards a closed mouth if `level` is `None`,
+    /// and returns the new phase. `thresholds` must be sorted from lowest to highest. The phase
+    /// returned is never higher than the number of thresholds, even if they changed since the
+    /// last update.
+    pub fn update(&mut self, level: Option<f32>, thresholds: &[Threshold], now: Instant) -> usize {
+        // the mouth may have had more levels before an expression change
+        self.phase = self.phase.min(thresholds.len());
+
+        let mut target = self.phase;
+        match level {
+            Some(level) => {
+                while target < thresholds.len() && level >= thresholds[target].enter {
+                    target += 1;
+                }
+                while target > 0 && level < thresholds[target - 1].exit {
+                    target -= 1;
+                }
+            }
+            None => target = 0,
+        }
+
+        // keep the mouth slightly open for a moment before closing it
+        if target == 0 && self.phase > 0 && !thresholds.is_empty() {
+            let silent_since = *self.silent_since.get_or_insert(now);
+            if now.duration_since(silent_since) < self.release {
+                target = 1;
+            }
+        } else {
+            self.silent_since = None;
+        }
+
+        let hold = self
+            .phase_holds
+            .get(self.phase)
+            .copied()
+            .unwrap_or(self.hold);
+        if target != self.phase && now.duration_since(self.changed_at) >= hold {
+            self.phase = target;
+            self.changed_at = now;
+        }
+
+        self.phase
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLDS: [Threshold; 2] = [
+        Threshold {
+            enter: 1.0,
+            exit: 0.8,
+        },
+        Threshold {
+            enter: 2.0,
+            exit: 1.8,
+        },
+    ];
+
+    fn timing(hold_ms: f32, release_ms: f32) -> PhaseTiming {
+        PhaseTiming {
+            hold_ms,
+            phase_hold_ms: Vec::new(),
+            release_ms,
+        }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn hovering_between_enter_and_exit_does_not_flicker() {
+        let start = Instant::now();
+        let mut selector = PhaseSelector::new(&timing(0.0, 0.0), Duration::ZERO, start);
+        assert_eq!(selector.update(Some(1.0), &THRESHOLDS, start + ms(10)), 1);
+
+        for step in 2..100 {
+            let level = if step % 2 == 0 { 0.85 } else { 1.05 };
+            assert_eq!(
+                selector.update(Some(level), &THRESHOLDS, start + ms(step * 10)),
+                1
+            );
+        }
+        assert_eq!(selector.update(Some(2.0), &THRESHOLDS, start + ms(1000)), 2);
+        for step in 101..200 {
+            let level = if step % 2 == 0 { 1.85 } else { 2.05 };
+            assert_eq!(
+                selector.update(Some(level), &THRESHOLDS, start + ms(step * 10)),
+                2
+            );
+        }
+    }
+
+    #[test]
+    fn hold_time_blocks_a_change() {
+        let start = Instant::now();
+        let mut selector = PhaseSelector::new(&timing(100.0, 0.0), Duration::ZERO, start);
+        assert_eq!(selector.update(Some(1.0), &THRESHOLDS, start + ms(200)), 1);
+        assert_eq!(selector.update(Some(0.0), &THRESHOLDS, start + ms(250)), 1);
+        assert_eq!(selector.update(Some(2.0), &THRESHOLDS, start + ms(290)), 1);
+        assert_eq!(selector.update(Some(2.0), &THRESHOLDS, start + ms(305)), 2);
+    }
+
+    #[test]
+    fn phases_can_be_held_for_different_times() {
+        let start = Instant::now();
+        let timing = PhaseTiming {
+            phase_hold_ms: vec![0.0, 0.0, 300.0],
+            ..timing(50.0, 0.0)
+        };
+        let mut selector = PhaseSelector::new(&timing, Duration::ZERO, start);
+        assert_eq!(selector.update(Some(1.0), &THRESHOLDS, start), 1);
+        assert_eq!(selector.update(Some(2.0), &THRESHOLDS, start + ms(10)), 2);
+        assert_eq!(selector.update(Some(0.0), &THRESHOLDS, start + ms(200)), 2);
+        assert_eq!(selector.update(Some(0.0), &THRESHOLDS, start + ms(320)), 0);
+    }
+
+    #[test]
+    fn release_tail_holds_the_first_phase_before_closing() {
+        let start = Instant::now();
+        let mut selector = PhaseSelector::new(&timing(0.0, 120.0), Duration::ZERO, start);
+        assert_eq!(selector.update(Some(2.0), &THRESHOLDS, start), 2);
+        assert_eq!(selector.update(None, &THRESHOLDS, start + ms(10)), 1);
+        assert_eq!(selector.update(None, &THRESHOLDS, start + ms(100)), 1);
+        assert_eq!(selector.update(None, &THRESHOLDS, start + ms(140)), 0);
+    }
+
+    #[test]
+    fn switching_to_fewer_levels_never_returns_a_missing_phase() {
+        let start = Instant::now();
+        let five: Vec<_> = (1..=5)
+            .map(|level| Threshold::with_hysteresis(level as f32, 0.2))
+            .collect();
+        let mut selector = PhaseSelector::new(&timing(100.0, 120.0), Duration::ZERO, start);
+        assert_eq!(selector.update(Some(5.0), &five, start + ms(200)), 5);
+
+        // still within the hold time of phase 5
+        assert_eq!(selector.update(Some(5.0), &THRESHOLDS, start + ms(210)), 2);
+        assert_eq!(selector.update(None, &[], start + ms(220)), 0);
+        assert_eq!(selector.update(None, &[], start + ms(400)), 0);
+    }
+}
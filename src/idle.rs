@@ -0,0 +1,240 @@
+use std::time::{Duration, Instant};
+
+use eframe::{
+    egui::{Align2, FontId, Rect, Ui, Vec2},
+    epaint::Color32,
+};
+use serde::Deserialize;
+
+use crate::ExpressionChange;
+
+/// How long a single "Z" takes to float up and fade away, in seconds.
+const ZZZ_SECONDS: f32 = 3.0;
+
+/// How many "Z"s float up at once.
+const ZZZ_COUNT: usize = 3;
+
+/// Settings for what the character does on its own while the streamer is quiet.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdleConfig {
+    /// How long the character has to be quiet before it starts yawning and glancing around, in
+    /// seconds.
+    pub actions_after_secs: f32,
+
+    /// The shortest time between idle actions, in seconds.
+    pub min_action_gap_secs: f32,
+
+    /// The longest time between idle actions, in seconds.
+    pub max_action_gap_secs: f32,
+
+    /// The character's yawn. The character doesn't yawn if this isn't set.
+    pub yawn: Option<YawnConfig>,
+
+    /// How long the character glances to the side, in milliseconds. The character doesn't
+    /// glance if this is 0.
+    pub glance_ms: f32,
+
+    /// How long the character has to be quiet before it falls asleep, in minutes. The character
+    /// never falls asleep if this isn't set.
+    pub sleep_after_mins: Option<f32>,
+
+    /// The expression to use while asleep. The eyes are closed while asleep either way.
+    pub sleep_expression: Option<ExpressionChange>,
+
+    /// Whether "Z"s float up from the character while it's asleep.
+    pub zzz: bool,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            actions_after_secs: 20.0,
+            min_action_gap_secs: 8.0,
+            max_action_gap_secs: 20.0,
+            yawn: None,
+            glance_ms: 900.0,
+            sleep_after_mins: None,
+            sleep_expression: None,
+            zzz: true,
+        }
+    }
+}
+
+/// Settings for the character's yawn.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct YawnConfig {
+    /// The expression to use while yawning.
+    pub expression: ExpressionChange,
+
+    /// How long a yawn lasts, in milliseconds.
+    #[serde(default = "YawnConfig::default_duration_ms")]
+    pub duration_ms: f32,
+}
+
+impl YawnConfig {
+    fn default_duration_ms() -> f32 {
+        2500.0
+    }
+}
+
+/// Something the character does on its own while idle.
+enum IdleAction {
+    Yawn,
+
+    /// Looking in a direction, from -1 to 1 across and down.
+    Glance(Vec2),
+}
+
+/// What the character is doing on its own at the moment.
+pub struct IdleState<'a> {
+    /// The expression to use, if the character is yawning or asleep.
+    pub expression: Option<&'a ExpressionChange>,
+
+    /// The direction to look in, if the character is glancing.
+    pub look: Option<Vec2>,
+
+    /// The time at which the character fell asleep, if it's asleep.
+    pub asleep_since: Option<Instant>,
+
+    /// Whether "Z"s should float up from the character.
+    pub zzz: bool,
+}
+
+impl IdleState<'_> {
+    /// A character that isn't doing anything on its own.
+    const AWAKE: Self = Self {
+        expression: None,
+        look: None,
+        asleep_since: None,
+        zzz: false,
+    };
+}
+
+/// Decides when the character yawns, glances around and falls asleep while the streamer is quiet.
+pub struct IdleScheduler {
+    config: IdleConfig,
+
+    /// The action in progress and the time at which it ends.
+    action: Option<(IdleAction, Instant)>,
+
+    /// The time at which the next action starts, once the character is idle.
+    next_action: Option<Instant>,
+}
+
+impl IdleScheduler {
+    pub fn new(config: &IdleConfig) -> Self {
+        Self {
+            config: config.clone(),
+            action: None,
+            next_action: None,
+        }
+    }
+
+    /// Returns a random time between actions.
+    fn random_gap(&self) -> Duration {
+        let min = self.config.min_action_gap_secs.max(0.0);
+        let max = self.config.max_action_gap_secs.max(min);
+        Duration::from_secs_f32(min + rand::random::<f32>() * (max - min))
+    }
+
+    /// Starts and stops idle actions for a character that's been quiet since `quiet_since`, and
+    /// returns what the character is doing. Everything stops as soon as the character speaks.
+    pub fn update(&mut self, quiet_since: Instant, now: Instant) -> IdleState<'_> {
+        let quiet = now.duration_since(quiet_since);
+        if quiet.as_secs_f32() < self.config.actions_after_secs {
+            self.action = None;
+            self.next_action = None;
+            return IdleState::AWAKE;
+        }
+
+        if let Some(minutes) = self.config.sleep_after_mins {
+            let sleep_after = Duration::from_secs_f32(minutes.max(0.0) * 60.0);
+            if quiet >= sleep_after {
+                self.action = None;
+                return IdleState {
+                    expression: self.config.sleep_expression.as_ref(),
+                    look: None,
+                    asleep_since: Some(quiet_since + sleep_after),
+                    zzz: self.config.zzz,
+                };
+            }
+        }
+
+        if self.action.as_ref().is_some_and(|(_, end)| now >= *end) {
+            self.action = None;
+        }
+
+        let next_action = match self.next_action {
+            Some(next_action) => next_action,
+            None => *self.next_action.insert(now + self.random_gap()),
+        };
+        if self.action.is_none() && now >= next_action {
+            self.action = self.random_action(now);
+            self.next_action = Some(now + self.random_gap());
+        }
+
+        match &self.action {
+            Some((IdleAction::Yawn, _)) => IdleState {
+                expression: self.config.yawn.as_ref().map(|yawn| &yawn.expression),
+                ..IdleState::AWAKE
+            },
+            Some((IdleAction::Glance(direction), _)) => IdleState {
+                look: Some(*direction),
+                ..IdleState::AWAKE
+            },
+            None => IdleState::AWAKE,
+        }
+    }
+
+    /// Picks one of the configured actions at random, starting at `now`.
+    fn random_action(&self, now: Instant) -> Option<(IdleAction, Instant)> {
+        let yawn = self.config.yawn.as_ref().map(|yawn| {
+            (
+                IdleAction::Yawn,
+                now + Duration::from_secs_f32(yawn.duration_ms.max(0.0) / 1000.0),
+            )
+        });
+
+        let glance = (self.config.glance_ms > 0.0).then(|| {
+            let side = if rand::random::<bool>() { 1.0 } else { -1.0 };
+            let direction = Vec2::new(side, rand::random::<f32>() * 0.6 - 0.3);
+            (
+                IdleAction::Glance(direction),
+                now + Duration::from_secs_f32(self.config.glance_ms / 1000.0),
+            )
+        });
+
+        match (yawn, glance) {
+            (Some(yawn), Some(glance)) => Some(if rand::random::<bool>() { yawn } else { glance }),
+            (yawn, glance) => yawn.or(glance),
+        }
+    }
+}
+
+/// Paints "Z"s floating up from the top right of the given rectangle, for a character that's
+/// been asleep for `asleep` so far.
+pub fn paint_zzz(ui: &Ui, rect: Rect, asleep: Duration) {
+    let painter = ui.painter();
+    let start = rect.center_top() + Vec2::new(rect.width() * 0.2, rect.height() * 0.25);
+
+    for i in 0..ZZZ_COUNT {
+        // each "Z" starts a little after the one before it
+        let t = asleep.as_secs_f32() / ZZZ_SECONDS - i as f32 / ZZZ_COUNT as f32;
+        if t < 0.0 {
+            continue;
+        }
+        let t = t.fract();
+
+        let pos = start + Vec2::new((t * 6.0).sin() * 8.0 + t * 30.0, -t * 80.0);
+        let alpha = (1.0 - t) * (t * 5.0).min(1.0);
+        painter.text(
+            pos,
+            Align2::CENTER_CENTER,
+            "Z",
+            FontId::proportional(18.0 + t * 14.0),
+            Color32::from_rgb(60, 60, 120).gamma_multiply(alpha),
+        );
+    }
+}
@@ -1,18 +1,132 @@
 use std::collections::HashMap;
 
-use eframe::egui::{Context, Key};
+use eframe::egui::{Context, Key, Vec2};
+use serde::Deserialize;
 
 use crate::ExpressionChange;
 
+/// Hotkeys for one character, as written in the config file.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HotkeyConfig {
+    /// The key that shuts the character's eyes while held.
+    pub force_blink: Option<KeyName>,
+
+    /// The key that mutes or unmutes the character.
+    pub mute: Option<KeyName>,
+
+    /// A key that must be held for the character to hear anything.
+    pub push_to_talk: Option<KeyName>,
+
+    /// Keys that switch the character's expression when pressed.
+    pub switches: HashMap<KeyName, ExpressionChange>,
+
+    /// Keys that change the character's expression while held.
+    pub holds: HashMap<KeyName, ExpressionChange>,
+
+    /// Keys that make the character look in a direction while held, such as towards chat. The
+    /// direction is given from -1 to 1 across and down.
+    pub looks: HashMap<KeyName, [f32; 2]>,
+}
+
+/// A key, written by its name in the config file, such as `"F1"` or `"A"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "String")]
+pub struct KeyName(pub Key);
+
+impl TryFrom<String> for KeyName {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Key::from_name(&name)
+            .map(Self)
+            .ok_or_else(|| format!("unknown key '{name}'"))
+    }
+}
+
+/// Hotkeys that affect the whole app rather than a single character.
+pub struct AppHotkeys {
+    pub calibrate_key: Key,
+    pub meters_key: Key,
+    pub diagnostics_key: Key,
+}
+
+impl AppHotkeys {
+    /// Returns whether noise floor calibration should start.
+    pub fn should_calibrate(&self, ctx: &Context) -> bool {
+        ctx.input(|i| i.key_pressed(self.calibrate_key))
+    }
+
+    /// Returns whether the channel meters should be shown or hidden.
+    pub fn should_toggle_meters(&self, ctx: &Context) -> bool {
+        ctx.input(|i| i.key_pressed(self.meters_key))
+    }
+
+    /// Returns whether the problems found at startup should be shown or hidden.
+    pub fn should_toggle_diagnostics(&self, ctx: &Context) -> bool {
+        ctx.input(|i| i.key_pressed(self.diagnostics_key))
+    }
+}
+
+impl Default for AppHotkeys {
+    fn default() -> Self {
+        Self {
+            calibrate_key: Key::Insert,
+            meters_key: Key::Home,
+            diagnostics_key: Key::PageUp,
+        }
+    }
+}
+
 pub struct ExpressionHotkeyManager {
-    pub force_blink_key: Key,
+    pub force_blink_key: Option<Key>,
+    pub mute_key: Option<Key>,
+    pub push_to_talk_key: Option<Key>,
     pub expression_switches: HashMap<Key, ExpressionChange>,
     pub expression_holds: HashMap<Key, ExpressionChange>,
+    pub look_holds: HashMap<Key, Vec2>,
 }
 
 impl ExpressionHotkeyManager {
+    pub fn from_config(config: &HotkeyConfig) -> Self {
+        Self {
+            force_blink_key: config.force_blink.map(|k| k.0),
+            mute_key: config.mute.map(|k| k.0),
+            push_to_talk_key: config.push_to_talk.map(|k| k.0),
+            expression_switches: config
+                .switches
+                .iter()
+                .map(|(key, expression)| (key.0, expression.clone()))
+                .collect(),
+            expression_holds: config
+                .holds
+                .iter()
+                .map(|(key, expression)| (key.0, expression.clone()))
+                .collect(),
+            look_holds: config
+                .looks
+                .iter()
+                .map(|(key, &direction)| (key.0, Vec2::from(direction)))
+                .collect(),
+        }
+    }
+
     pub fn should_force_blink(&self, ctx: &Context) -> bool {
-        ctx.input(|i| i.key_down(self.force_blink_key))
+        self.force_blink_key
+            .is_some_and(|key| ctx.input(|i| i.key_down(key)))
+    }
+
+    /// Returns whether the character should be muted or unmuted.
+    pub fn should_toggle_mute(&self, ctx: &Context) -> bool {
+        self.mute_key
+            .is_some_and(|key| ctx.input(|i| i.key_pressed(key)))
+    }
+
+    /// Returns whether push-to-talk keeps the character from hearing anything, which is the case
+    /// while a push-to-talk key is set but not held.
+    pub fn is_push_to_talk_released(&self, ctx: &Context) -> bool {
+        self.push_to_talk_key
+            .is_some_and(|key| !ctx.input(|i| i.key_down(key)))
     }
 
     /// Returns the expression to switch to if its key was pressed, or None if no key is pressed.
@@ -38,4 +152,110 @@ impl ExpressionHotkeyManager {
             }
         })
     }
+
+    /// Returns the direction to look in if its key is held down.
+    pub fn get_look_direction(&self, ctx: &Context) -> Option<Vec2> {
+        self.look_holds
+            .iter()
+            .find_map(|(key, &direction)| ctx.input(|i| i.key_down(*key)).then_some(direction))
+    }
+}
+
+/// The built-in hotkeys, used by characters that don't configure their own.
+impl Default for ExpressionHotkeyManager {
+    fn default() -> Self {
+        Self {
+            force_blink_key: Some(Key::F12),
+            mute_key: Some(Key::End),
+            push_to_talk_key: None,
+            expression_switches: HashMap::from([
+                (
+                    Key::F1,
+                    ExpressionChange {
+                        eyes: Some("normal".to_string()),
+                        head: Some("happy".to_string()),
+                    },
+                ),
+                (
+                    Key::F2,
+                    ExpressionChange {
+                        eyes: Some("angry".to_string()),
+                        head: Some("happy".to_string()),
+                    },
+                ),
+                (
+                    Key::F3,
+                    ExpressionChange {
+                        eyes: Some("sad".to_string()),
+                        head: Some("happy".to_string()),
+                    },
+                ),
+                (
+                    Key::F4,
+                    ExpressionChange {
+                        eyes: Some("dreamy".to_string()),
+                        head: Some("happy".to_string()),
+                    },
+                ),
+                (
+                    Key::F5,
+                    ExpressionChange {
+                        eyes: Some("normal".to_string()),
+                        head: Some("frown".to_string()),
+                    },
+                ),
+                (
+                    Key::F6,
+                    ExpressionChange {
+                        eyes: Some("angry".to_string()),
+                        head: Some("frown".to_string()),
+                    },
+                ),
+                (
+                    Key::F7,
+                    ExpressionChange {
+                        eyes: Some("sad".to_string()),
+                        head: Some("frown".to_string()),
+                    },
+                ),
+                (
+                    Key::F8,
+                    ExpressionChange {
+                        eyes: Some("dreamy".to_string()),
+                        head: Some("frown".to_string()),
+                    },
+                ),
+                (
+                    Key::F9,
+                    ExpressionChange {
+                        eyes: Some("sad".to_string()),
+                        head: Some("wavy".to_string()),
+                    },
+                ),
+                (
+                    Key::F10,
+                    ExpressionChange {
+                        eyes: Some("wide".to_string()),
+                        head: Some("wavy".to_string()),
+                    },
+                ),
+                (
+                    Key::F11,
+                    ExpressionChange {
+                        eyes: Some("happy".to_string()),
+                        head: Some("happy".to_string()),
+                    },
+                ),
+                (
+                    Key::F12,
+                    ExpressionChange {
+                        eyes: Some("tight".to_string()),
+                        head: Some("happy".to_string()),
+                    },
+                ),
+            ]),
+            expression_holds: HashMap::new(),
+            look_holds: HashMap::new(),
+        }
+    }
 }
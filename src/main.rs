@@ -1,163 +1,208 @@
 mod audio;
+mod calibration;
+mod character;
+mod cli;
+mod config;
+mod crossfade;
+mod diagnostics;
 mod eyes;
+mod flipbook;
 mod head;
+mod idle;
 mod keys;
+mod meters;
+mod reaction;
+mod rules;
+mod threshold;
 
-use cpal::Stream;
+use calibration::CalibrationConfig;
+use character::{Character, CharacterConfig};
+use cli::Args;
+use config::Config;
+use diagnostics::Diagnostic;
 use eframe::{
     Frame,
-    egui::{self, CentralPanel, Context, Image, Key, Ui, Vec2},
+    egui::{self, CentralPanel, Context, FontId, Rect, Ui, Vec2},
     epaint::Color32,
 };
-use eyes::Eyes;
-use head::Head;
-use std::{collections::HashMap, time::Instant};
+use serde::Deserialize;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args = match Args::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{}", cli::USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if args.help {
+        println!("{}", cli::USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    if args.list_devices {
+        return match audio::list_input_devices() {
+            Ok(devices) => {
+                for (index, device) in devices.iter().enumerate() {
+                    println!("{index}: {device}");
+                }
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("couldn't list input devices: {e}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let config = match Config::load(args.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // command line options override the first character's input
+    let mut characters = config.characters();
+    let first_audio = &mut characters[0].audio;
+    if let Some(device) = args.device {
+        first_audio.device = Some(device);
+    }
+    if let Some(path) = args.file {
+        first_audio.file = Some(audio::FileConfig {
+            path,
+            pacing: audio::Pacing::RealTime,
+            looping: false,
+        });
+    }
+
+    if let Some(path) = args.analyze {
+        return match audio::analyze_file(&path, first_audio) {
+            Ok(frames) => {
+                println!("time_secs,level_dbfs,intensity,peak_dbfs,is_speech,viseme,pitch_hz");
+                for frame in frames {
+                    println!(
+                        "{:.3},{:.1},{},{:.1},{},{},{}",
+                        frame.time.as_secs_f32(),
+                        frame.level_dbfs,
+                        frame
+                            .intensity
+                            .map(|i| format!("{i:.2}"))
+                            .unwrap_or_default(),
+                        frame.peak_dbfs,
+                        frame.is_speech,
+                        frame.viseme.map(|v| format!("{v:?}")).unwrap_or_default(),
+                        frame
+                            .pitch_hz
+                            .map(|p| format!("{p:.1}"))
+                            .unwrap_or_default(),
+                    );
+                }
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                eprintln!("couldn't analyze {}: {e}", path.display());
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    // report typos in expression names and the like before they turn into silent fallbacks
+    let diagnostics = diagnostics::check(&characters);
+    for diagnostic in &diagnostics {
+        eprintln!("{diagnostic}");
+    }
+    if args.check {
+        return if diagnostics::has_errors(&diagnostics) {
+            ExitCode::FAILURE
+        } else {
+            println!("no errors found");
+            ExitCode::SUCCESS
+        };
+    }
 
-fn main() -> eframe::Result {
     let options = eframe::NativeOptions::default();
 
-    eframe::run_native(
+    let result = eframe::run_native(
         "muni-tuber",
         options,
         Box::new(|cc| {
             egui_extras::install_image_loaders(&cc.egui_ctx);
-            Ok(Box::<MuniTuberApp>::default())
+            Ok(Box::new(MuniTuberApp::new(
+                &characters,
+                &config.calibration,
+                args.calibrate,
+                diagnostics,
+            )))
         }),
-    )
+    );
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
 }
 
 struct MuniTuberApp<'a> {
-    /// The time at which the app started.
-    start: Instant,
-
-    /// The state of the audio input volume.
-    audio_state: audio::AudioState,
-
-    /// The image of the body to use.
-    body: Image<'a>,
+    /// The characters in the scene, from left to right.
+    characters: Vec<Character<'a>>,
 
-    /// The head of the character
-    head: Head<'a>,
+    /// The audio input threads of the characters, stored here so that they aren't stopped.
+    _audio_input: audio::AudioInput,
 
-    /// The eyes state of the character.
-    eyes: Eyes<'a>,
+    /// Hotkeys that affect every character.
+    hotkeys: keys::AppHotkeys,
 
-    /// The expression of the character.
-    expression: ExpressionState,
+    /// Whether the channel meters are shown.
+    show_meters: bool,
 
-    /// The hotkey manager for the character's expressions.
-    hotkey_manager: keys::ExpressionHotkeyManager,
+    /// Problems found in the setup at startup.
+    diagnostics: Vec<Diagnostic>,
 
-    /// The audio input stream, stored here so that it isn't dropped.
-    _audio_stream: Stream,
+    /// Whether the problems found at startup are shown. They're shown from the start only if any
+    /// of them is an error.
+    show_diagnostics: bool,
 }
 
-impl Default for MuniTuberApp<'_> {
-    fn default() -> Self {
-        let (audio_state, _audio_stream) = audio::start_default_stream();
+impl MuniTuberApp<'_> {
+    fn new(
+        characters: &[CharacterConfig],
+        calibration_config: &CalibrationConfig,
+        calibrate: bool,
+        diagnostics: Vec<Diagnostic>,
+    ) -> Self {
+        let (audio_states, _audio_input) = audio::AudioInput::start(
+            characters
+                .iter()
+                .map(|character| character.audio.clone())
+                .collect(),
+        );
+        let mut characters: Vec<_> = characters
+            .iter()
+            .zip(audio_states)
+            .enumerate()
+            .map(|(index, (character, audio_state))| {
+                Character::new(character, index, audio_state, calibration_config)
+            })
+            .collect();
 
-        let hotkey_manager = keys::ExpressionHotkeyManager {
-            force_blink_key: Key::F12,
-            expression_switches: HashMap::from([
-                (
-                    Key::F1,
-                    ExpressionChange {
-                        eyes: Some("normal".to_string()),
-                        head: Some("happy".to_string()),
-                    },
-                ),
-                (
-                    Key::F2,
-                    ExpressionChange {
-                        eyes: Some("angry".to_string()),
-                        head: Some("happy".to_string()),
-                    },
-                ),
-                (
-                    Key::F3,
-                    ExpressionChange {
-                        eyes: Some("sad".to_string()),
-                        head: Some("happy".to_string()),
-                    },
-                ),
-                (
-                    Key::F4,
-                    ExpressionChange {
-                        eyes: Some("dreamy".to_string()),
-                        head: Some("happy".to_string()),
-                    },
-                ),
-                (
-                    Key::F5,
-                    ExpressionChange {
-                        eyes: Some("normal".to_string()),
-                        head: Some("frown".to_string()),
-                    },
-                ),
-                (
-                    Key::F6,
-                    ExpressionChange {
-                        eyes: Some("angry".to_string()),
-                        head: Some("frown".to_string()),
-                    },
-                ),
-                (
-                    Key::F7,
-                    ExpressionChange {
-                        eyes: Some("sad".to_string()),
-                        head: Some("frown".to_string()),
-                    },
-                ),
-                (
-                    Key::F8,
-                    ExpressionChange {
-                        eyes: Some("dreamy".to_string()),
-                        head: Some("frown".to_string()),
-                    },
-                ),
-                (
-                    Key::F9,
-                    ExpressionChange {
-                        eyes: Some("sad".to_string()),
-                        head: Some("wavy".to_string()),
-                    },
-                ),
-                (
-                    Key::F10,
-                    ExpressionChange {
-                        eyes: Some("wide".to_string()),
-                        head: Some("wavy".to_string()),
-                    },
-                ),
-                (
-                    Key::F11,
-                    ExpressionChange {
-                        eyes: Some("happy".to_string()),
-                        head: Some("happy".to_string()),
-                    },
-                ),
-                (
-                    Key::F12,
-                    ExpressionChange {
-                        eyes: Some("tight".to_string()),
-                        head: Some("happy".to_string()),
-                    },
-                ),
-            ]),
-            expression_holds: HashMap::new(),
-        };
+        if calibrate {
+            characters.iter_mut().for_each(Character::start_calibration);
+        }
 
         Self {
-            start: Instant::now(),
-            audio_state,
-            _audio_stream,
-
-            body: Image::from_bytes("bytes://body", include_bytes!("assets/body.png")),
-
-            head: Default::default(),
-            eyes: Default::default(),
-            expression: Default::default(),
-            hotkey_manager,
+            characters,
+            _audio_input,
+            hotkeys: Default::default(),
+            show_meters: false,
+            show_diagnostics: diagnostics::has_errors(&diagnostics),
+            diagnostics,
         }
     }
 }
@@ -168,76 +213,60 @@ const POP_DURATION: f32 = 0.25;
 /// The influence of the pop animation on the character.
 const POP_AMOUNT: f32 = 2.0;
 
-impl MuniTuberApp<'_> {
-    fn paint(&mut self, ctx: &Context, ui: &mut Ui) {
-        let pop_value = {
-            // quadratic function
-            let x = self.head.get_last_speak_start().elapsed().as_secs_f32();
-            let a = -4.0 / POP_DURATION.powi(2);
-            let b = -a * POP_DURATION;
-
-            a * x.powi(2) + b * x
+impl eframe::App for MuniTuberApp<'_> {
+    fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
+        if self.hotkeys.should_calibrate(ctx) {
+            self.characters
+                .iter_mut()
+                .for_each(Character::start_calibration);
         }
-        .max(0.0);
-
-        let breath_value =
-            (self.start.elapsed().as_secs_f32() * 1.5).sin() + pop_value * POP_AMOUNT;
-        let breath_scale_x = 1.0 - breath_value / 200.0;
-        let breath_scale_y = 1.0 + breath_value / 200.0;
-
-        // draw body
-        // let image_to_ui_height_ratio = ui.max_rect().height() / body_size.y;
-        let Some(body_size) = self.body.load_and_calc_size(ui, ui.max_rect().size()) else {
-            return;
-        };
-
-        let response = ui.add(
-            self.body
-                .clone()
-                .maintain_aspect_ratio(false)
-                .fit_to_exact_size(body_size * Vec2::new(breath_scale_x, breath_scale_y)),
-        );
-
-        let rect = response.rect;
-
-        // get some variables
-        let should_force_blink = self.hotkey_manager.should_force_blink(ctx);
-        if let Some(new_expression) = self.hotkey_manager.get_expression(ctx) {
-            self.expression.apply(new_expression)
+        if self.hotkeys.should_toggle_meters(ctx) {
+            self.show_meters = !self.show_meters;
+        }
+        if self.hotkeys.should_toggle_diagnostics(ctx) {
+            self.show_diagnostics = !self.show_diagnostics;
         }
-        let temporary_expression = self.hotkey_manager.get_temporary_expression(ctx);
-        let head_to_use = temporary_expression
-            .and_then(|e| e.head.as_ref())
-            .unwrap_or(&self.expression.head);
-        let eyes_to_use = temporary_expression
-            .and_then(|e| e.eyes.as_ref())
-            .unwrap_or(&self.expression.eyes);
-
-        // draw head and eyes
-        let volume = *self.audio_state.volume.lock().unwrap();
-        self.head.paint(ui, rect, volume, head_to_use);
-        self.eyes.paint(ui, rect, eyes_to_use, should_force_blink);
-    }
-}
 
-impl eframe::App for MuniTuberApp<'_> {
-    fn update(&mut self, ctx: &Context, _frame: &mut Frame) {
         CentralPanel::default()
             .frame(egui::Frame {
                 fill: Color32::YELLOW,
                 ..Default::default()
             })
             .show(ctx, |ui| {
-                ui.with_layout(egui::Layout::bottom_up(egui::Align::Center), |ui| {
-                    self.paint(ctx, ui);
+                // characters stand side by side, each in its own column
+                ui.columns(self.characters.len(), |columns| {
+                    for (ui, character) in columns.iter_mut().zip(&mut self.characters) {
+                        ui.with_layout(egui::Layout::bottom_up(egui::Align::Center), |ui| {
+                            character.paint(ctx, ui);
+                        });
+                        character.paint_status(ui, self.show_meters);
+                    }
                 });
+
+                if self.show_diagnostics && !self.diagnostics.is_empty() {
+                    diagnostics::paint_overlay(ui, &self.diagnostics);
+                }
             });
         ctx.request_repaint();
     }
 }
 
+/// Paints a status badge in the top left corner of the given ui, such as "no mic", so that it's
+/// obvious why the character isn't reacting to speech.
+fn paint_badge(ui: &Ui, text: &str) {
+    let painter = ui.painter();
+    let galley =
+        painter.layout_no_wrap(text.to_string(), FontId::proportional(16.0), Color32::WHITE);
+
+    let text_pos = ui.max_rect().left_top() + Vec2::splat(12.0);
+    let badge = Rect::from_min_size(text_pos, galley.size()).expand(6.0);
+    painter.rect_filled(badge, 4.0, Color32::from_rgb(200, 40, 40));
+    painter.galley(text_pos, galley, Color32::WHITE);
+}
+
 /// A change in the expression of the character. `None` means no change to the expression.
-#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ExpressionChange {
     eyes: Option<String>,
     head: Option<String>,
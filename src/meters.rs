@@ -0,0 +1,94 @@
+use std::time::Instant;
+
+use eframe::{
+    egui::{Align2, FontId, Rect, Ui, Vec2, pos2},
+    epaint::Color32,
+};
+
+use crate::audio::AnalysisFrame;
+
+/// How quickly a meter falls after a peak, in dB per second.
+const FALL_DB_PER_SEC: f32 = 30.0;
+
+/// The level at the bottom of a meter, in dBFS.
+const FLOOR_DBFS: f32 = -60.0;
+
+/// The size of a single meter, in points.
+const METER_SIZE: Vec2 = Vec2::new(10.0, 80.0);
+
+/// The space between meters, in points.
+const METER_SPACING: f32 = 6.0;
+
+/// Level meters for every channel of the input, showing which channels feed the character.
+#[derive(Default)]
+pub struct ChannelMeters {
+    /// The level shown by each meter, in dBFS.
+    levels_dbfs: Vec<f32>,
+
+    /// Whether each channel feeds the character.
+    selected: Vec<bool>,
+
+    /// The time of the last update.
+    last_update: Option<Instant>,
+}
+
+impl ChannelMeters {
+    /// Moves the meters to the peaks of the given frames, letting them fall slowly otherwise.
+    pub fn update(&mut self, frames: &[AnalysisFrame], now: Instant) {
+        let fall = self
+            .last_update
+            .map_or(0.0, |t| (now - t).as_secs_f32() * FALL_DB_PER_SEC);
+        self.last_update = Some(now);
+
+        for level in &mut self.levels_dbfs {
+            *level = (*level - fall).max(FLOOR_DBFS);
+        }
+
+        for frame in frames {
+            let levels = &frame.channel_levels;
+            self.levels_dbfs
+                .resize(levels.peaks_dbfs().len(), FLOOR_DBFS);
+            self.selected.clear();
+            self.selected.extend_from_slice(levels.selected());
+
+            for (level, &peak) in self.levels_dbfs.iter_mut().zip(levels.peaks_dbfs()) {
+                *level = level.max(peak);
+            }
+        }
+    }
+
+    /// Paints the meters in the bottom right corner of the given ui. Channels that feed the
+    /// character are green, the others gray.
+    pub fn paint(&self, ui: &Ui) {
+        let painter = ui.painter();
+        let corner = ui.max_rect().right_bottom() - Vec2::splat(12.0);
+        let count = self.levels_dbfs.len() as f32;
+        let left = corner.x - count * (METER_SIZE.x + METER_SPACING) + METER_SPACING;
+
+        for (channel, (&level, &selected)) in
+            self.levels_dbfs.iter().zip(&self.selected).enumerate()
+        {
+            let x = left + channel as f32 * (METER_SIZE.x + METER_SPACING);
+            let meter = Rect::from_min_size(pos2(x, corner.y - METER_SIZE.y), METER_SIZE);
+            painter.rect_filled(meter, 2.0, Color32::from_black_alpha(160));
+
+            let fill = (1.0 - level / FLOOR_DBFS).clamp(0.0, 1.0);
+            let mut bar = meter;
+            bar.set_top(meter.bottom() - fill * meter.height());
+            let color = if selected {
+                Color32::from_rgb(60, 200, 80)
+            } else {
+                Color32::GRAY
+            };
+            painter.rect_filled(bar, 2.0, color);
+
+            painter.text(
+                meter.center_top() - Vec2::new(0.0, 2.0),
+                Align2::CENTER_BOTTOM,
+                (channel + 1).to_string(),
+                FontId::proportional(10.0),
+                Color32::BLACK,
+            );
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    f32::consts::TAU,
+    time::{Duration, Instant},
+};
+
+use eframe::{egui::Vec2, epaint::Color32};
+use serde::Deserialize;
+
+/// Settings for how the character reacts to yelling.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct YellConfig {
+    /// How long the reaction lasts after the character starts yelling, in milliseconds.
+    pub duration_ms: f32,
+
+    /// How far the character shakes, in points.
+    pub shake_px: f32,
+
+    /// How quickly the character shakes, in shakes per second.
+    pub shake_hz: f32,
+
+    /// How much bigger the character gets at the start of the reaction, as a fraction of its
+    /// size.
+    pub punch: f32,
+
+    /// The color the character flashes, as red, green and blue from 0 to 255. The character
+    /// doesn't change color if this isn't set.
+    pub tint: Option<[u8; 3]>,
+}
+
+impl Default for YellConfig {
+    fn default() -> Self {
+        Self {
+            duration_ms: 350.0,
+            shake_px: 4.0,
+            shake_hz: 25.0,
+            punch: 0.05,
+            tint: None,
+        }
+    }
+}
+
+/// The effects applied to the character at a given moment of a yell reaction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct YellEffects {
+    /// How far to move the character.
+    pub offset: Vec2,
+
+    /// How much to scale the character by.
+    pub scale: f32,
+
+    /// The color to tint the character's images with.
+    pub tint: Color32,
+}
+
+impl YellEffects {
+    /// No effects at all.
+    pub const NONE: Self = Self {
+        offset: Vec2::ZERO,
+        scale: 1.0,
+        tint: Color32::WHITE,
+    };
+}
+
+/// Shakes, punches and tints the character for a moment after it starts yelling.
+pub struct YellReaction {
+    config: YellConfig,
+
+    /// How long the reaction lasts.
+    duration: Duration,
+}
+
+impl YellReaction {
+    pub fn new(config: &YellConfig) -> Self {
+        Self {
+            config: *config,
+            duration: Duration::from_secs_f32(config.duration_ms.max(0.0) / 1000.0),
+        }
+    }
+
+    /// Returns the effects to apply at `now`, for a yell that started at `yell_start`, if any.
+    pub fn effects(&self, yell_start: Option<Instant>, now: Instant) -> YellEffects {
+        let Some(elapsed) = yell_start.map(|start| now.duration_since(start)) else {
+            return YellEffects::NONE;
+        };
+        if elapsed >= self.duration {
+            return YellEffects::NONE;
+        }
+
+        // every effect starts at full strength and dies down over the reaction
+        let t = elapsed.as_secs_f32();
+        let strength = (1.0 - t / self.duration.as_secs_f32()).powi(2);
+
+        // shake along two different frequencies so the motion doesn't look like a straight line
+        let phase = TAU * self.config.shake_hz * t;
+        let offset = Vec2::new(phase.sin(), (phase * 1.7 + 1.0).sin()) * self.config.shake_px;
+
+        let tint = match self.config.tint {
+            Some([r, g, b]) => Color32::WHITE.lerp_to_gamma(Color32::from_rgb(r, g, b), strength),
+            None => Color32::WHITE,
+        };
+
+        YellEffects {
+            offset: offset * strength,
+            scale: 1.0 + self.config.punch * strength,
+            tint,
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::ExpressionChange;
+
+/// A rule that changes the character's expression while the voice stays within a pitch range.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PitchRule {
+    /// The lowest pitch the rule applies to, in Hz. Unbounded if not set.
+    #[serde(default)]
+    pub min_hz: Option<f32>,
+
+    /// The highest pitch the rule applies to, in Hz. Unbounded if not set.
+    #[serde(default)]
+    pub max_hz: Option<f32>,
+
+    /// How long the pitch must stay in range before the expression changes, in milliseconds.
+    #[serde(default = "default_hold_ms")]
+    pub hold_ms: f32,
+
+    /// How long the expression is kept after the pitch was last in range, in milliseconds. This
+    /// also bridges unvoiced sounds and short pauses while the rule is building up.
+    #[serde(default = "default_release_ms")]
+    pub release_ms: f32,
+
+    /// The expression to show while the rule applies.
+    pub expression: ExpressionChange,
+}
+
+fn default_hold_ms() -> f32 {
+    300.0
+}
+
+fn default_release_ms() -> f32 {
+    600.0
+}
+
+impl PitchRule {
+    fn matches(&self, pitch_hz: f32) -> bool {
+        self.min_hz.is_none_or(|min| pitch_hz >= min)
+            && self.max_hz.is_none_or(|max| pitch_hz <= max)
+    }
+
+    fn hold(&self) -> Duration {
+        Duration::from_secs_f32(self.hold_ms.max(0.0) / 1000.0)
+    }
+
+    fn release(&self) -> Duration {
+        Duration::from_secs_f32(self.release_ms.max(0.0) / 1000.0)
+    }
+}
+
+/// A rule whose pitch range the voice has been in.
+#[derive(Clone, Copy)]
+struct RuleMatch {
+    /// The index of the rule.
+    rule: usize,
+
+    /// When the voice entered the rule's range.
+    since: Instant,
+
+    /// When the voice was last in the rule's range.
+    last_seen: Instant,
+}
+
+impl RuleMatch {
+    /// Returns whether the voice has been out of the rule's range for longer than its release.
+    fn is_expired(&self, rules: &[PitchRule], now: Instant) -> bool {
+        now.duration_since(self.last_seen) > rules[self.rule].release()
+    }
+}
+
+/// Picks a temporary expression based on the pitch of the voice, so that the character can react
+/// to how something is said without anyone pressing a hotkey.
+pub struct PitchRules {
+    rules: Vec<PitchRule>,
+
+    /// The rule the voice is currently building up towards.
+    candidate: Option<RuleMatch>,
+
+    /// The rule whose expression is being shown.
+    active: Option<RuleMatch>,
+}
+
+impl PitchRules {
+    pub fn new(rules: Vec<PitchRule>) -> Self {
+        Self {
+            rules,
+            candidate: None,
+            active: None,
+        }
+    }
+
+    /// Updates the rules with the current pitch, or `None` if the voice isn't pitched right now,
+    /// and returns the expression to show, if any. The first rule matching the pitch wins.
+    pub fn update(&mut self, pitch_hz: Option<f32>, now: Instant) -> Option<&ExpressionChange> {
+        let rule = pitch_hz.and_then(|hz| self.rules.iter().position(|r| r.matches(hz)));
+
+        match (rule, &mut self.candidate) {
+            (Some(rule), Some(candidate)) if candidate.rule == rule => candidate.last_seen = now,
+            (Some(rule), _) => {
+                self.candidate = Some(RuleMatch {
+                    rule,
+                    since: now,
+                    last_seen: now,
+                })
+            }
+            (None, Some(candidate)) if candidate.is_expired(&self.rules, now) => {
+                self.candidate = None
+            }
+            (None, _) => {}
+        }
+
+        if let Some(candidate) = self.candidate
+            && now.duration_since(candidate.since) >= self.rules[candidate.rule].hold()
+        {
+            self.active = Some(candidate);
+        }
+
+        if let Some(active) = &self.active
+            && active.is_expired(&self.rules, now)
+        {
+            self.active = None;
+        }
+
+        self.active
+            .map(|active| &self.rules[active.rule].expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> Result<Vec<PitchRule>, toml::de::Error> {
+        #[derive(Deserialize)]
+        struct Rules {
+            pitch_rules: Vec<PitchRule>,
+        }
+        toml::from_str::<Rules>(toml).map(|rules| rules.pitch_rules)
+    }
+
+    fn rules(toml: &str) -> PitchRules {
+        PitchRules::new(parse(toml).unwrap())
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    const HIGH_AND_LOW: &str = r#"
+        [[pitch_rules]]
+        min_hz = 300.0
+        hold_ms = 100.0
+        release_ms = 200.0
+        expression = { eyes = "wide" }
+
+        [[pitch_rules]]
+        max_hz = 110.0
+        hold_ms = 0.0
+        release_ms = 0.0
+        expression = { eyes = "dreamy" }
+    "#;
+
+    fn eyes(expression: Option<&ExpressionChange>) -> Option<&str> {
+        expression.and_then(|e| e.eyes.as_deref())
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(
+            parse("[[pitch_rules]]\nmin_hertz = 300.0\nexpression = { eyes = \"wide\" }").is_err()
+        );
+        assert!(
+            parse("[[pitch_rules]]\nmin_hz = 300.0\nexpression = { eyse = \"wide\" }").is_err()
+        );
+        assert!(parse("[[pitch_rules]]\nmin_hz = 300.0\neyes = \"wide\"").is_err());
+    }
+
+    #[test]
+    fn expression_changes_after_the_hold() {
+        let start = Instant::now();
+        let mut rules = rules(HIGH_AND_LOW);
+        assert_eq!(eyes(rules.update(Some(350.0), start)), None);
+        assert_eq!(eyes(rules.update(Some(350.0), start + ms(50))), None);
+        assert_eq!(
+            eyes(rules.update(Some(350.0), start + ms(101))),
+            Some("wide")
+        );
+    }
+
+    #[test]
+    fn short_dips_out_of_range_dont_restart_the_hold() {
+        let start = Instant::now();
+        let mut rules = rules(HIGH_AND_LOW);
+        rules.update(Some(350.0), start);
+        assert_eq!(eyes(rules.update(None, start + ms(60))), None);
+        assert_eq!(
+            eyes(rules.update(Some(350.0), start + ms(101))),
+            Some("wide")
+        );
+    }
+
+    #[test]
+    fn expression_lingers_for_the_release() {
+        let start = Instant::now();
+        let mut rules = rules(HIGH_AND_LOW);
+        rules.update(Some(350.0), start);
+        rules.update(Some(350.0), start + ms(101));
+        assert_eq!(eyes(rules.update(None, start + ms(301))), Some("wide"));
+        assert_eq!(eyes(rules.update(None, start + ms(302))), None);
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let start = Instant::now();
+        let mut rules = rules(HIGH_AND_LOW);
+        assert_eq!(eyes(rules.update(Some(100.0), start)), Some("dreamy"));
+        assert_eq!(eyes(rules.update(Some(200.0), start + ms(1))), None);
+    }
+}
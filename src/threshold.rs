@@ -0,0 +1,79 @@
+use serde::Deserialize;
+
+/// A level the input must reach for the mouth to open further. The mouth only closes again once
+/// the input falls below the lower exit level, so a level hovering around the threshold doesn't
+/// make the mouth flicker.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(from = "ThresholdConfig")]
+pub struct Threshold {
+    /// The level at or above which the mouth opens to this phase.
+    pub enter: f32,
+
+    /// The level below which the mouth leaves this phase again.
+    pub exit: f32,
+}
+
+impl Threshold {
+    /// Returns a threshold entered at `enter` and left `hysteresis` below it.
+    pub fn with_hysteresis(enter: f32, hysteresis: f32) -> Self {
+        Self {
+            enter,
+            exit: enter - hysteresis,
+        }
+    }
+}
+
+/// A threshold as written in the config file: either a single level, or separate enter and exit
+/// levels.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ThresholdConfig {
+    Level(f32),
+    Range { enter: f32, exit: f32 },
+}
+
+impl From<ThresholdConfig> for Threshold {
+    fn from(config: ThresholdConfig) -> Self {
+        match config {
+            ThresholdConfig::Level(level) => Self {
+                enter: level,
+                exit: level,
+            },
+            ThresholdConfig::Range { enter, exit } => Self {
+                enter,
+                exit: exit.min(enter),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Thresholds {
+        level: Threshold,
+        range: Threshold,
+    }
+
+    #[test]
+    fn thresholds_parse_as_a_level_or_a_range() {
+        let thresholds: Thresholds =
+            toml::from_str("level = 0.2\nrange = { enter = 0.55, exit = 0.45 }").unwrap();
+        assert_eq!(
+            thresholds.level,
+            Threshold {
+                enter: 0.2,
+                exit: 0.2
+            }
+        );
+        assert_eq!(
+            thresholds.range,
+            Threshold {
+                enter: 0.55,
+                exit: 0.45
+            }
+        );
+    }
+}